@@ -1,17 +1,97 @@
 use std::fmt::{Display, Error, Formatter};
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
 
-pub type Symbol = String;
+pub type Symbol = Rc<str>;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Script(pub Vec<Expr>);
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Expr {
+    Nil,
     Bool(bool),
     Integer(i64),
+    /// An exact fraction in lowest terms, with `den > 1`.
+    Rational { num: i64, den: i64 },
     Float(f64),
+    Str(String),
+    Char(char),
     Symbol(Symbol),
-    List(Vec<Expr>),
+    // shared, so that cloning a sub-tree (e.g. a function body) is cheap
+    List(Rc<[Expr]>),
+    /// A dotted pair `(car . cdr)` whose `cdr` isn't a list, so `(1 2 . 3)` is
+    /// a pair of 1 and the pair `(2 . 3)`.
+    Pair(Rc<Expr>, Rc<Expr>),
+}
+
+impl Expr {
+    /// The expression read as `(exprs... . tail)`: a list if `tail` is one,
+    /// otherwise nested pairs.
+    pub fn dotted(exprs: Vec<Expr>, tail: Expr) -> Expr {
+        exprs.into_iter().rev().fold(tail, |tail, expr| match tail {
+            Expr::List(list) => Expr::List(std::iter::once(expr).chain(list.iter().cloned()).collect()),
+            _ => Expr::Pair(Rc::new(expr), Rc::new(tail)),
+        })
+    }
+}
+
+/// Reduces a fraction to lowest terms with a positive denominator, or `None`
+/// if the denominator is zero or either part doesn't fit in an `i64`.
+pub fn reduce_fraction(num: i128, den: i128) -> Option<(i64, i64)> {
+    if den == 0 {
+        return None;
+    }
+    let (mut a, mut b) = (num.abs(), den.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    let sign = den.signum();
+    let num = i64::try_from(sign * num / a).ok()?;
+    let den = i64::try_from(sign * den / a).ok()?;
+    Some((num, den))
+}
+
+/// Structural equality, with floats compared by their bit patterns so that
+/// equality is reflexive (a `NaN` literal equals itself, though `0.0` and
+/// `-0.0` differ), as `Eq` and `Hash` require.
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Expr::Nil, Expr::Nil) => true,
+            (Expr::Bool(x), Expr::Bool(y)) => x == y,
+            (Expr::Integer(x), Expr::Integer(y)) => x == y,
+            (Expr::Rational { num: a, den: b }, Expr::Rational { num: c, den: d }) => a == c && b == d,
+            (Expr::Float(x), Expr::Float(y)) => x.to_bits() == y.to_bits(),
+            (Expr::Str(x), Expr::Str(y)) => x == y,
+            (Expr::Char(x), Expr::Char(y)) => x == y,
+            (Expr::Symbol(x), Expr::Symbol(y)) => x == y,
+            (Expr::List(x), Expr::List(y)) => x == y,
+            (Expr::Pair(a, b), Expr::Pair(c, d)) => a == c && b == d,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Expr {}
+
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Expr::Nil => {},
+            Expr::Bool(b) => b.hash(state),
+            Expr::Integer(i) => i.hash(state),
+            Expr::Rational { num, den } => (num, den).hash(state),
+            Expr::Float(f) => f.to_bits().hash(state),
+            Expr::Str(s) => s.hash(state),
+            Expr::Char(c) => c.hash(state),
+            Expr::Symbol(sym) => sym.hash(state),
+            Expr::List(list) => list.hash(state),
+            Expr::Pair(car, cdr) => (car, cdr).hash(state),
+        }
+    }
 }
 
 impl Display for Script {
@@ -26,16 +106,51 @@ impl Display for Script {
 impl Display for Expr {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         match self {
+            Expr::Nil => write!(fmt, "nil"),
             Expr::Bool(b) => write!(fmt, "{}", b),
             Expr::Integer(i) => write!(fmt, "{}", i),
+            Expr::Rational { num, den } => write!(fmt, "{}/{}", num, den),
             Expr::Float(f) => write!(fmt, "{}", f),
+            Expr::Str(s) => {
+                write!(fmt, "\"")?;
+                for c in s.chars() {
+                    match c {
+                        '\n' => write!(fmt, "\\n")?,
+                        '\t' => write!(fmt, "\\t")?,
+                        '"' => write!(fmt, "\\\"")?,
+                        '\\' => write!(fmt, "\\\\")?,
+                        _ => write!(fmt, "{}", c)?,
+                    }
+                }
+                write!(fmt, "\"")
+            },
+            Expr::Char(c) => match c {
+                '\n' => write!(fmt, "\\newline"),
+                ' ' => write!(fmt, "\\space"),
+                '\t' => write!(fmt, "\\tab"),
+                _ => write!(fmt, "\\{}", c),
+            },
             Expr::Symbol(sym) => write!(fmt, "{}", sym),
-            Expr::List(list) => {
-                write!(fmt, "(")?;
-                for item in list.iter() {
-                    write!(fmt, "{} ", item)?;
+            // `(quote expr)` is shown in the same `'expr` shorthand it's read from
+            Expr::List(list) => match list.as_ref() {
+                [Expr::Symbol(sym), expr] if &**sym == "quote" => write!(fmt, "'{}", expr),
+                _ => {
+                    write!(fmt, "(")?;
+                    for (i, item) in list.iter().enumerate() {
+                        if i > 0 { write!(fmt, " ")?; }
+                        write!(fmt, "{}", item)?;
+                    }
+                    write!(fmt, ")")
+                },
+            },
+            Expr::Pair(car, cdr) => {
+                write!(fmt, "({}", car)?;
+                let mut cdr = cdr;
+                while let Expr::Pair(next, rest) = cdr.as_ref() {
+                    write!(fmt, " {}", next)?;
+                    cdr = rest;
                 }
-                write!(fmt, ")")
+                write!(fmt, " . {})", cdr)
             },
         }
     }
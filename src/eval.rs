@@ -1,68 +1,422 @@
-use std::borrow::BorrowMut;
-use std::cell::RefCell;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::fmt::{Display, Error, Formatter};
+use std::f64::consts;
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::Instant;
 
 use crate::ast;
+use crate::parser;
+use math::{cos, log, pow, sin, sqrt, tan};
 
+#[derive(Clone, Debug)]
 pub enum Value<'a> {
+    Nil,
     Bool(bool),
     Integer(i64),
+    /// An exact fraction in lowest terms, with `den > 1`; arithmetic producing a
+    /// whole number gives an integer instead.
+    Rational { num: i64, den: i64 },
     Float(f64),
-    List(Vec<Value<'a>>),
+    Str(String),
+    Char(char),
+    Symbol(ast::Symbol),
+    List(Vec<Rc<Value<'a>>>),
+    /// A cons cell whose `cdr` isn't a list; consing onto a list gives a list.
+    Pair(Rc<Value<'a>>, Rc<Value<'a>>),
+    /// Keys are held as the expressions denoting them (see `unquoted`), as
+    /// values themselves can't be hashed.
+    Map(HashMap<ast::Expr, Rc<Value<'a>>>),
+    /// Raw binary data, made with `bytes`.
+    Bytes(Vec<u8>),
     Func(Func<'a>),
+    Promise(Rc<Promise<'a>>),
 }
 
+/// An expression whose evaluation is put off until it's forced, after which
+/// the value is kept so it's only ever evaluated once.
+pub struct Promise<'a> {
+    expr: ast::Expr,
+    env: Env<'a>,
+    value: RefCell<Option<Rc<Value<'a>>>>,
+    // set while the expression is being evaluated, to catch it forcing itself
+    forcing: Cell<bool>,
+}
+
+/// Cloning a user-defined function shares its captured scope rather than
+/// copying it, just as cloning an `Env` does.
+#[derive(Clone)]
 pub enum Func<'a> {
+    /// A function implemented in Rust, given its arguments unevaluated. Those
+    /// that evaluate them as ordinary arguments must do so strictly left to
+    /// right, each at most once, as `Env::eval` does for user-defined functions.
     BuiltIn {
         name: &'a str,
-        func: fn(&mut Env<'a>, &[ast::Expr]) -> Result<Rc<Value<'a>>, String>,
+        func: fn(&mut Env<'a>, &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError>,
+        doc: &'a str,
+    },
+    /// A built-in that works on its arguments' syntax, such as `quote` or `def`,
+    /// and may leave an expression in tail position for `Env::eval` to carry on
+    /// with, rather than evaluating it on the native stack. Having no values to
+    /// work on, these can't be passed to `map`, `apply` and the like.
+    SpecialForm {
+        name: &'a str,
+        func: fn(&mut Env<'a>, &[ast::Expr]) -> Result<Tail<'a>, EvalError>,
+        doc: &'a str,
     },
     UserDefined {
         params: Vec<ast::Expr>,
-        body: ast::Expr,
-    }
+        body: Vec<ast::Expr>,
+        env: Rc<Env<'a>>,
+    },
+    /// Wraps another function, caching its results by argument. Calls with an
+    /// argument that's a function (or contains one) aren't cached.
+    Memoized {
+        func: Rc<Value<'a>>,
+        cache: Rc<RefCell<HashMap<Vec<ast::Expr>, Rc<Value<'a>>>>>,
+    },
 }
 
+pub enum Tail<'a> {
+    Done(Rc<Value<'a>>),
+    Eval(Env<'a>, ast::Expr),
+}
+
+/// A scope of bindings, chained to its enclosing scope via `outer`.
+///
+/// Cloning an `Env` is cheap and yields a handle onto the *same* bindings,
+/// which is what lets closures see definitions made after they were created.
 #[derive(Clone)]
 pub struct Env<'a> {
-    data: HashMap<ast::Symbol, Rc<Value<'a>>>,
-    outer: Option<Rc<RefCell<Env<'a>>>>, // &'a Env<'a>>,
+    data: Rc<RefCell<HashMap<ast::Symbol, Rc<Value<'a>>>>>,
+    outer: Option<Rc<Env<'a>>>,
+    // shared by every scope descending from the same root
+    depth: Rc<Cell<usize>>,
+    recursion_limit: usize,
+    // files part-way through being evaluated by `load`, also shared by every scope
+    loading: Rc<RefCell<Vec<PathBuf>>>,
+    // where `print` and friends write to, also shared by every scope
+    output: Rc<RefCell<dyn Write + 'a>>,
+    // significant digits floats are printed to, if not in full
+    float_precision: Option<usize>,
+}
+
+/// Why evaluation failed. Displays as the message shown to the user, which is
+/// also what `try` binds.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalError {
+    UnknownSymbol(ast::Symbol),
+    /// A built-in was given a value of the wrong type.
+    TypeMismatch { name: String, expected: &'static str, found: String },
+    /// A function was given the wrong number (or shape) of arguments.
+    Arity { name: String, expected: String },
+    /// The source couldn't be parsed, at the given 1-based line and column.
+    Parse { line: usize, column: usize, message: String },
+    DivisionByZero,
+    IntegerOverflow,
+    RecursionLimit,
+    /// Raised by the program itself, with `error` or `assert`.
+    UserError(String),
+    /// Any other failure, described by its message.
+    Other(String),
+}
+
+impl EvalError {
+    fn arity(name: &str, expected: &str) -> Self {
+        EvalError::Arity { name: name.to_string(), expected: expected.to_string() }
+    }
+
+    fn type_mismatch(name: &str, expected: &'static str, found: &Value) -> Self {
+        EvalError::TypeMismatch { name: name.to_string(), expected, found: found.to_string() }
+    }
+}
+
+impl From<String> for EvalError {
+    fn from(msg: String) -> Self {
+        EvalError::Other(msg)
+    }
+}
+
+impl From<&str> for EvalError {
+    fn from(msg: &str) -> Self {
+        EvalError::Other(msg.to_string())
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match self {
+            EvalError::UnknownSymbol(sym) => write!(fmt, "Unknown symbol '{}'", sym),
+            EvalError::TypeMismatch { name, expected, found } =>
+                write!(fmt, "Cannot apply '{}' to non-{} '{}'", name, expected, found),
+            EvalError::Arity { name, expected } => write!(fmt, "'{}' takes {}", name, expected),
+            EvalError::Parse { line, column, message } =>
+                write!(fmt, "parse error at line {}, column {}: {}", line, column, message),
+            EvalError::DivisionByZero => write!(fmt, "Division by zero"),
+            EvalError::IntegerOverflow => write!(fmt, "integer overflow"),
+            EvalError::RecursionLimit => write!(fmt, "Maximum recursion depth exceeded"),
+            EvalError::UserError(msg) | EvalError::Other(msg) => write!(fmt, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// The bindings of a single scope at some point in time, to be put back with
+/// `Env::restore`.
+pub struct EnvSnapshot<'a>(HashMap<ast::Symbol, Rc<Value<'a>>>);
+
+/// How deeply evaluation may nest by default. Each level takes around 7 KB of
+/// native stack in an unoptimised build (about a seventh of that optimised), so
+/// this fits in the 8 MB of a typical main thread with room to spare.
+pub const DEFAULT_RECURSION_LIMIT: usize = 800;
+
+/// Marks a nested evaluation as in progress until dropped.
+struct CallGuard(Rc<Cell<usize>>);
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() - 1);
+    }
 }
 
+// each built-in is named by its symbol and documented for `help`, as in
+// `insert_builtin!(env, "+", addition => "...")`
 macro_rules! insert_builtin {
-    ($data:ident, $name:expr, $func:ident) => {
-        insert_builtin!($data, $name, $func, stringify!($func));
-    };
-    ($data:ident, $name:expr, $func:ident, $tag:expr) => {
+    ($data:ident, $name:expr, $func:ident => $doc:expr) => {
         $data.insert(
             ast::Symbol::from($name),
             Rc::new(
                 Value::Func(
                     Func::BuiltIn {
-                        name: $tag,
+                        name: $name,
                         func: $func,
+                        doc: $doc,
                     }
                 )
             )
         );
-    }
+    };
+}
+
+macro_rules! insert_special_form {
+    ($data:ident, $name:expr, $func:ident => $doc:expr) => {
+        $data.insert(
+            ast::Symbol::from($name),
+            Rc::new(
+                Value::Func(
+                    Func::SpecialForm {
+                        name: $name,
+                        func: $func,
+                        doc: $doc,
+                    }
+                )
+            )
+        );
+    };
 }
 
 impl<'a> Default for Env<'a> {
 
     fn default() -> Self {
         let mut env = Self::new();
-        insert_builtin!(env, "def", def);
-        insert_builtin!(env, "fn", func, "fn");
-        insert_builtin!(env, "if", ifdef, "if");
-        insert_builtin!(env, "=", equals);
-        insert_builtin!(env, "+", addition);
-        insert_builtin!(env, "-", subtraction);
-        insert_builtin!(env, "*", multiplication);
-        insert_builtin!(env, "/", division);
-        insert_builtin!(env, "<", less_than);
+        insert_special_form!(env, "def", def =>
+            "(def name value) binds name to value in the current scope");
+        insert_special_form!(env, "defn", defn =>
+            "(defn name (params...) body...) defines a function, like (def name (fn (params...) body...))");
+        insert_special_form!(env, "set!", set =>
+            "(set! name value) rebinds an existing name in the innermost scope that binds it");
+        insert_special_form!(env, "fn", func =>
+            "(fn (params...) body...) makes a function; '& rest' collects extra arguments, and :key parameters may be passed by keyword");
+        insert_special_form!(env, "if", ifdef =>
+            "(if cond then [else]) evaluates then if cond is true, else otherwise (or gives nil)");
+        insert_special_form!(env, "cond", cond =>
+            "(cond (test body...)... [(else body...)]) evaluates the body of the first clause whose test is true");
+        insert_special_form!(env, "case", case =>
+            "(case key (datum body...)... [(else body...)]) evaluates the body of the first clause whose unevaluated datum = key");
+        insert_special_form!(env, "when", when =>
+            "(when cond body...) evaluates the body if cond is true, else gives nil");
+        insert_special_form!(env, "unless", unless =>
+            "(unless cond body...) evaluates the body if cond is false, else gives nil");
+        insert_special_form!(env, "let", letdef =>
+            "(let ((name value)...) body) binds names for the body, evaluating every value before binding any; (let loop (...) body) also binds loop as a function of those names");
+        insert_special_form!(env, "let*", let_star =>
+            "(let* ((name value)...) body) binds names in turn for the body, so each value can refer to the names before it");
+        insert_special_form!(env, "try", try_catch =>
+            "(try expr [msg] handler) gives expr, or handler if expr fails, with the error message bound to msg");
+        insert_builtin!(env, "error", error =>
+            "(error value) fails with value as the error message");
+        insert_builtin!(env, "assert", assert =>
+            "(assert cond [message]) fails unless cond is true");
+        insert_special_form!(env, "while", whiledef =>
+            "(while cond body...) evaluates the body for as long as cond is true");
+        insert_special_form!(env, "dotimes", dotimes =>
+            "(dotimes (name n) body...) evaluates the body with name bound to 0 up to n - 1");
+        insert_special_form!(env, "begin", begin =>
+            "(begin expr...) evaluates each expression in turn, giving the value of the last");
+        insert_special_form!(env, "do", begin =>
+            "(do expr...) evaluates each expression in turn, giving the value of the last");
+        insert_special_form!(env, "quote", quote =>
+            "(quote expr), or 'expr, gives expr as data without evaluating it");
+        insert_builtin!(env, "gensym", gensym =>
+            "(gensym [prefix]) makes a new, unique symbol");
+        insert_builtin!(env, "=", equals =>
+            "(= a b...) tests whether all arguments are equal");
+        insert_builtin!(env, "!=", not_equals =>
+            "(!= a b) tests whether two arguments differ");
+        insert_builtin!(env, "+", addition =>
+            "(+ n...) adds numbers");
+        insert_builtin!(env, "-", subtraction =>
+            "(- n...) subtracts the remaining numbers from the first, or negates one");
+        insert_builtin!(env, "*", multiplication =>
+            "(* n...) multiplies numbers");
+        insert_builtin!(env, "/", division =>
+            "(/ n...) divides the first number by the rest, truncating integers, or inverts one");
+        insert_builtin!(env, "mod", modulo =>
+            "(mod n...) gives the remainder of dividing the first integer by the rest");
+        insert_builtin!(env, "bit-and", bit_and =>
+            "(bit-and n...) gives the bitwise and of integers");
+        insert_builtin!(env, "bit-or", bit_or =>
+            "(bit-or n...) gives the bitwise or of integers");
+        insert_builtin!(env, "bit-xor", bit_xor =>
+            "(bit-xor n...) gives the bitwise exclusive or of integers");
+        insert_builtin!(env, "bit-not", bit_not =>
+            "(bit-not n) flips every bit of an integer");
+        insert_builtin!(env, "bit-shift", bit_shift =>
+            "(bit-shift n count) shifts an integer's bits left by count, or right if count is negative");
+        insert_builtin!(env, "min", min =>
+            "(min n...) gives the smallest number");
+        insert_builtin!(env, "max", max =>
+            "(max n...) gives the largest number");
+        insert_builtin!(env, "abs", abs =>
+            "(abs n) gives the absolute value of a number");
+        insert_builtin!(env, "floor", floor =>
+            "(floor n) rounds a number down to a whole number");
+        insert_builtin!(env, "ceil", ceil =>
+            "(ceil n) rounds a number up to a whole number");
+        insert_builtin!(env, "round", round =>
+            "(round n) rounds a number to the nearest whole number, halves away from zero");
+        insert_builtin!(env, "to-int", to_int =>
+            "(to-int n) converts a number to an integer, truncating towards zero");
+        insert_builtin!(env, "to-float", to_float =>
+            "(to-float n) converts a number to a float");
+        insert_builtin!(env, "sqrt", sqrt =>
+            "(sqrt n) gives the square root of a number");
+        insert_builtin!(env, "pow", pow =>
+            "(pow base exp) raises base to the power exp");
+        insert_builtin!(env, "log", log =>
+            "(log n [base]) gives the logarithm of n, natural unless base is given");
+        insert_builtin!(env, "sin", sin =>
+            "(sin n) gives the sine of an angle in radians");
+        insert_builtin!(env, "cos", cos =>
+            "(cos n) gives the cosine of an angle in radians");
+        insert_builtin!(env, "tan", tan =>
+            "(tan n) gives the tangent of an angle in radians");
+        // ordinary bindings, so programs are free to redefine them
+        env.insert(ast::Symbol::from("pi"), Rc::new(Value::Float(consts::PI)));
+        env.insert(ast::Symbol::from("e"), Rc::new(Value::Float(consts::E)));
+        insert_builtin!(env, "<", less_than =>
+            "(< n...) tests whether numbers are strictly increasing");
+        insert_builtin!(env, ">", greater_than =>
+            "(> n...) tests whether numbers are strictly decreasing");
+        insert_builtin!(env, "<=", less_than_or_equal =>
+            "(<= n...) tests whether numbers are non-decreasing");
+        insert_builtin!(env, ">=", greater_than_or_equal =>
+            "(>= n...) tests whether numbers are non-increasing");
+        insert_builtin!(env, "and", and =>
+            "(and b...) tests whether all arguments are true, stopping at the first false one");
+        insert_builtin!(env, "or", or =>
+            "(or b...) tests whether any argument is true, stopping at the first true one");
+        insert_builtin!(env, "not", not =>
+            "(not b) negates a boolean");
+        insert_builtin!(env, "type", type_of =>
+            "(type value) gives the name of a value's type as a string");
+        insert_builtin!(env, "nil?", is_nil =>
+            "(nil? value) tests whether a value is nil");
+        insert_builtin!(env, "bool?", is_bool =>
+            "(bool? value) tests whether a value is a boolean");
+        insert_builtin!(env, "number?", is_number =>
+            "(number? value) tests whether a value is a number");
+        insert_builtin!(env, "list?", is_list =>
+            "(list? value) tests whether a value is a list");
+        insert_builtin!(env, "fn?", is_func =>
+            "(fn? value) tests whether a value is a function");
+        insert_builtin!(env, "help", help =>
+            "(help f) describes a function");
+        insert_builtin!(env, "print", print =>
+            "(print value...) writes values to the output, separated by spaces");
+        insert_builtin!(env, "println", println =>
+            "(println value...) writes values to the output, separated by spaces, then a newline");
+        insert_builtin!(env, "pprint", pprint =>
+            "(pprint value) writes a value to the output, splitting long lists across lines");
+        insert_builtin!(env, "time", time =>
+            "(time expr) evaluates expr, writing how long that took to stderr");
+        insert_builtin!(env, "read-line", read_line =>
+            "(read-line) reads a line from stdin, or gives nil at the end of input");
+        insert_builtin!(env, "read-file", read_file =>
+            "(read-file path) gives the contents of a file as a string");
+        insert_builtin!(env, "load", load =>
+            "(load path) evaluates the expressions in a source file");
+        insert_builtin!(env, "str-concat", str_concat =>
+            "(str-concat s...) joins strings together");
+        insert_builtin!(env, "format", format =>
+            "(format template value...) replaces each {} in a template with the next value; {{ and }} are literal braces");
+        insert_builtin!(env, "str-length", str_length =>
+            "(str-length s) gives the number of characters in a string");
+        insert_builtin!(env, "number->str", number_to_str =>
+            "(number->str n) formats a number as a string");
+        insert_builtin!(env, "str->number", str_to_number =>
+            "(str->number s) parses a string as a number");
+        insert_builtin!(env, "substring", substring =>
+            "(substring s start end) gives the characters of s from start up to end");
+        insert_builtin!(env, "bytes", bytes =>
+            "(bytes n...) makes a byte string of integers from 0 to 255");
+        insert_builtin!(env, "byte-ref", byte_ref =>
+            "(byte-ref b i) gives the byte at index i of a byte string, as an integer");
+        insert_builtin!(env, "bytes-length", bytes_length =>
+            "(bytes-length b) gives the number of bytes in a byte string");
+        insert_builtin!(env, "list", list =>
+            "(list value...) makes a list of its arguments");
+        insert_builtin!(env, "car", car =>
+            "(car list) gives the first element of a list");
+        insert_builtin!(env, "cdr", cdr =>
+            "(cdr list) gives all but the first element of a list");
+        insert_builtin!(env, "cons", cons =>
+            "(cons value list) makes a list with value in front of the elements of list, or a dotted pair if list isn't one");
+        insert_builtin!(env, "append", append =>
+            "(append list...) joins lists together");
+        insert_builtin!(env, "nth", nth =>
+            "(nth i list) gives the element of a list at index i, counting from 0");
+        insert_builtin!(env, "len", len =>
+            "(len coll) gives the length of a list or string");
+        insert_builtin!(env, "map", map =>
+            "(map f list) applies f to each element of a list, giving a list of the results");
+        insert_builtin!(env, "filter", filter =>
+            "(filter f list) gives the elements of a list for which f is true");
+        insert_builtin!(env, "reduce", reduce =>
+            "(reduce f init list) combines init with each element of a list in turn using f");
+        insert_builtin!(env, "sort", sort =>
+            "(sort list [less]) sorts a list of numbers, strings or chars ascending, or by less, a function telling whether its first argument goes before its second");
+        insert_builtin!(env, "apply", apply =>
+            "(apply f value... list) calls f with the values and the elements of list as arguments");
+        insert_builtin!(env, "hash-map", hash_map =>
+            "(hash-map key value...) makes a map from alternating keys and values");
+        insert_builtin!(env, "hash-get", hash_get =>
+            "(hash-get map key [default]) gives the value for key in a map, or default (or nil) if it's absent");
+        insert_builtin!(env, "hash-set", hash_set =>
+            "(hash-set map key value) gives a copy of a map with key bound to value");
+        insert_special_form!(env, "delay", delay =>
+            "(delay expr) makes a promise to evaluate expr when it's first forced");
+        insert_builtin!(env, "force", force =>
+            "(force promise) gives the value of a promise, evaluating it if that hasn't happened yet");
+        insert_builtin!(env, "memoize", memoize =>
+            "(memoize f) makes a version of f which caches its results by argument");
         env
     }
 }
@@ -71,251 +425,2169 @@ impl<'a> Env<'a> {
 
     pub fn new() -> Self {
         Self {
-            data: HashMap::new(),
+            data: Rc::new(RefCell::new(HashMap::new())),
             outer: None,
+            depth: Rc::new(Cell::new(0)),
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            loading: Rc::new(RefCell::new(Vec::new())),
+            output: Rc::new(RefCell::new(io::stdout())),
+            float_precision: None,
+        }
+    }
+
+    /// Sets how deeply evaluation may nest before it fails, rather than
+    /// overflowing the native stack. Every expression evaluated while another
+    /// is still in progress (an argument, say, or the body of a non-tail call)
+    /// counts as one level; tail calls don't.
+    ///
+    /// The limit must suit the stack of the thread evaluating: a level takes
+    /// several KB (see `DEFAULT_RECURSION_LIMIT`), so only raise it for code
+    /// running on a correspondingly bigger stack.
+    pub fn with_recursion_limit(mut self, limit: usize) -> Self {
+        self.recursion_limit = limit;
+        self
+    }
+
+    /// Sends the output of `print`, `println` and `pprint` to `output` rather
+    /// than stdout, e.g. an `Rc<RefCell<Vec<u8>>>` the caller keeps a clone of
+    /// to read back what was printed.
+    pub fn with_writer(mut self, output: Rc<RefCell<dyn Write + 'a>>) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Rounds floats to `digits` significant digits when printing them, with
+    /// `print` and friends or `Env::display`, rather than showing them in full.
+    /// Values keep their full precision, and `format` and `number->str` ignore
+    /// this.
+    pub fn with_float_precision(mut self, digits: Option<usize>) -> Self {
+        self.float_precision = digits.map(|digits| digits.max(1));
+        self
+    }
+
+    /// Shows a value as `print` would in this environment.
+    pub fn display(&self, value: &Value<'a>) -> String {
+        with_precision(value, self.float_precision)
+    }
+
+    fn with_outer(outer: Rc<Env<'a>>) -> Self {
+        Self {
+            data: Rc::new(RefCell::new(HashMap::new())),
+            depth: Rc::clone(&outer.depth),
+            recursion_limit: outer.recursion_limit,
+            loading: Rc::clone(&outer.loading),
+            output: Rc::clone(&outer.output),
+            float_precision: outer.float_precision,
+            outer: Some(outer),
+        }
+    }
+
+    fn enter_call(&self) -> Result<CallGuard, EvalError> {
+        let depth = self.depth.get();
+        if depth >= self.recursion_limit {
+            return Err(EvalError::RecursionLimit);
         }
+        self.depth.set(depth + 1);
+        Ok(CallGuard(Rc::clone(&self.depth)))
     }
 
     fn insert(&mut self, key: ast::Symbol, value: Rc<Value<'a>>) {
-        self.data.insert(key, value);
+        self.data.borrow_mut().insert(key, value);
+    }
+
+    /// Every visible binding, walking outward through enclosing scopes; a name
+    /// shadowed by an inner scope is only reported once.
+    pub fn bindings(&self) -> Vec<(ast::Symbol, Rc<Value<'a>>)> {
+        let mut bindings: HashMap<ast::Symbol, Rc<Value<'a>>> = HashMap::new();
+        let mut scope = Some(self);
+        while let Some(env) = scope {
+            for (key, value) in env.data.borrow().iter() {
+                bindings.entry(key.clone()).or_insert_with(|| Rc::clone(value));
+            }
+            scope = env.outer.as_deref();
+        }
+        let mut bindings: Vec<_> = bindings.into_iter().collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// Captures this scope's bindings (not those of enclosing scopes). Values
+    /// are shared rather than copied, so this is cheap.
+    pub fn snapshot(&self) -> EnvSnapshot<'a> {
+        EnvSnapshot(self.data.borrow().clone())
+    }
+
+    /// Puts back the bindings captured by `snapshot`, undoing any definitions or
+    /// reassignments in this scope made since.
+    pub fn restore(&mut self, snapshot: EnvSnapshot<'a>) {
+        *self.data.borrow_mut() = snapshot.0;
+    }
+
+    /// Whether any binding in this scope has been added, removed or rebound
+    /// since the snapshot was taken.
+    pub fn changed_since(&self, snapshot: &EnvSnapshot<'a>) -> bool {
+        let data = self.data.borrow();
+        data.len() != snapshot.0.len() || data.iter().any(|(key, value)| {
+            !snapshot.0.get(key).is_some_and(|old| Rc::ptr_eq(old, value))
+        })
     }
 
     fn get(&self, key: &ast::Symbol) -> Option<Rc<Value<'a>>> {
-        match self.data.get(key) {
+        match self.data.borrow().get(key) {
             Some(val) => Some(Rc::clone(val)),
-            None => self.outer.as_ref()?.borrow().get(key),
+            None => self.outer.as_ref()?.get(key),
         }
     }
 
-    pub fn eval(&mut self, expr: &ast::Expr) -> Result<Rc<Value<'a>>, String> {
-        match expr {
-            ast::Expr::Bool(b) => Ok(Rc::new(Value::Bool(*b))),
-            ast::Expr::Integer(i) => Ok(Rc::new(Value::Integer(*i))),
-            ast::Expr::Float(f) => Ok(Rc::new(Value::Float(*f))),
-            ast::Expr::Symbol(sym) => match self.get(sym) {
-                Some(val) => Ok(val.clone()),
-                None => { Err(format!("Unknown symbol '{}'", sym)) },
-            },
-            ast::Expr::List(list) => {
-                let (first, rest) = list.split_first().ok_or("List cannot be empty")?;
-                let res = self.eval(first)?;
-                match res.as_ref() {
-                    Value::Func(f) => match f {
-                        Func::BuiltIn { func, .. } => {
-                            let value = (*func)(self, rest)?;
-                            Ok(value.clone())
-                        },
-                        Func::UserDefined { params, body } => {
-                            if params.len() != rest.len() {
-                                return Err("Incorrect number of arguments provided".to_string());
-                            }
-                            let mut new_env = Env {
-                                data: HashMap::new(),
-                                outer: Some(Rc::new(RefCell::new(self.clone()))),
-                            };
-                            for (param, arg) in params.iter().zip(rest.iter()) {
-                                match param {
-                                    ast::Expr::Symbol(sym) => {
-                                        let arg_val = self.borrow_mut().eval(arg)?;
-                                        new_env.borrow_mut().insert(ast::Symbol::from(sym), arg_val);
-                                        Ok(())
-                                    },
-                                    _ => Err("..."),
-                                }?;
-                            }
-                            new_env.borrow_mut().eval(body)
+    /// Rebinds `key` in the innermost scope already binding it, returning
+    /// `false` (and binding nothing) if no scope does.
+    fn set(&self, key: &ast::Symbol, value: Rc<Value<'a>>) -> bool {
+        if let Some(slot) = self.data.borrow_mut().get_mut(key) {
+            *slot = value;
+            return true;
+        }
+        match &self.outer {
+            Some(outer) => outer.set(key, value),
+            None => false,
+        }
+    }
+
+    pub fn eval(&mut self, expr: &ast::Expr) -> Result<Rc<Value<'a>>, EvalError> {
+        // expressions in tail position are picked up by this loop rather than
+        // evaluated recursively, so tail calls don't grow the native stack
+        let mut env = self.clone();
+        let mut expr = Cow::Borrowed(expr);
+        // each native level of evaluation counts once towards the recursion
+        // limit, however many tail calls it then goes through
+        let mut call = None;
+        loop {
+            let list = match expr.as_ref() {
+                ast::Expr::Nil => return Ok(Rc::new(Value::Nil)),
+                ast::Expr::Bool(b) => return Ok(Rc::new(Value::Bool(*b))),
+                ast::Expr::Integer(i) => return Ok(Rc::new(Value::Integer(*i))),
+                ast::Expr::Rational { num, den } => return Ok(Rc::new(Value::Rational { num: *num, den: *den })),
+                ast::Expr::Float(f) => return Ok(Rc::new(Value::Float(*f))),
+                ast::Expr::Str(s) => return Ok(Rc::new(Value::Str(s.clone()))),
+                ast::Expr::Char(c) => return Ok(Rc::new(Value::Char(*c))),
+                ast::Expr::Symbol(sym) => return match env.get(sym) {
+                    Some(val) => Ok(val.clone()),
+                    // keywords such as `:x` evaluate to themselves
+                    None if is_keyword(sym) => Ok(Rc::new(Value::Symbol(Rc::clone(sym)))),
+                    None => Err(EvalError::UnknownSymbol(Rc::clone(sym))),
+                },
+                ast::Expr::List(list) => {
+                    if call.is_none() {
+                        call = Some(env.enter_call()?);
+                    }
+                    list
+                },
+                ast::Expr::Pair(..) => return Err(format!("Cannot evaluate dotted pair '{}'", expr).into()),
+            };
+            let (first, rest) = list.split_first().ok_or("List cannot be empty")?;
+            let res = env.eval(first)?;
+            let tail = match res.as_ref() {
+                Value::Func(f) => match f {
+                    Func::BuiltIn { func, .. } => return (*func)(&mut env, rest),
+                    Func::SpecialForm { func, .. } => (*func)(&mut env, rest)?,
+                    Func::UserDefined { params, body, env: outer } => {
+                        // arguments are evaluated left to right, which programs
+                        // with side effects (`set!`, `print`...) may rely on
+                        let mut args = Vec::with_capacity(rest.len());
+                        for arg in rest {
+                            args.push(env.eval(arg)?);
                         }
+                        call_user_defined(params, body, outer, args)?
                     },
-                    _ => Err(format!("{} is not a function", first)),
-                }
+                    Func::Memoized { .. } => {
+                        let mut args = Vec::with_capacity(rest.len());
+                        for arg in rest {
+                            args.push(env.eval(arg)?);
+                        }
+                        return env.apply(&res, args);
+                    },
+                },
+                _ => return Err(EvalError::type_mismatch(&first.to_string(), "function", &res)),
+            };
+            match tail {
+                Tail::Done(value) => return Ok(value),
+                Tail::Eval(next_env, next_expr) => {
+                    env = next_env;
+                    expr = Cow::Owned(next_expr);
+                },
             }
         }
     }
+
+    /// Evaluates each expression in a source file in this scope, returning the
+    /// value of the last one, as the `load` built-in does.
+    pub fn load_file(&mut self, path: &str) -> Result<Rc<Value<'a>>, EvalError> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("Cannot load '{}': {}", path, err))?;
+        let source = parser::strip_shebang(&source);
+        let script = parser::ScriptParser::new()
+            .parse(source)
+            .map_err(|err| format!("Cannot load '{}': {}", path, parser::format_error(source, &err)))?;
+        // the file could be read, so it can be canonicalized
+        let canonical = fs::canonicalize(path)
+            .map_err(|err| format!("Cannot load '{}': {}", path, err))?;
+        if self.loading.borrow().contains(&canonical) {
+            return Err(format!("Cannot load '{}' while it is already being loaded", path).into());
+        }
+        self.loading.borrow_mut().push(canonical);
+        let mut res = Ok(Rc::new(Value::Nil));
+        for expr in script.0.iter() {
+            res = self.eval(expr);
+            if res.is_err() { break; }
+        }
+        self.loading.borrow_mut().pop();
+        res
+    }
+
+    /// Calls a function value with arguments that have already been evaluated.
+    /// Special forms work on syntax rather than values, so can't be applied.
+    pub fn apply(&mut self, func: &Value<'a>, args: Vec<Rc<Value<'a>>>) -> Result<Rc<Value<'a>>, EvalError> {
+        // held until any tail expression has been evaluated too, as recursion
+        // through `map` and friends comes back in here
+        let _call = self.enter_call()?;
+        let tail = match func {
+            Value::Func(Func::BuiltIn { func, .. }) => {
+                let (mut scratch, exprs) = self.scratch_args(args);
+                return (*func)(&mut scratch, &exprs);
+            },
+            // these work on their arguments' syntax, which values don't have
+            Value::Func(Func::SpecialForm { name, .. }) =>
+                return Err(format!("Cannot apply special form '{}' to evaluated arguments", name).into()),
+            Value::Func(Func::UserDefined { params, body, env }) => call_user_defined(params, body, env, args)?,
+            Value::Func(Func::Memoized { func, cache }) => {
+                let key: Option<Vec<_>> = args.iter().map(|arg| unquoted(arg)).collect();
+                if let Some(value) = key.as_ref().and_then(|key| cache.borrow().get(key).cloned()) {
+                    return Ok(value);
+                }
+                let value = self.apply(func, args)?;
+                if let Some(key) = key {
+                    cache.borrow_mut().insert(key, Rc::clone(&value));
+                }
+                return Ok(value);
+            },
+            _ => return Err(EvalError::type_mismatch("apply", "function", func)),
+        };
+        match tail {
+            Tail::Done(value) => Ok(value),
+            Tail::Eval(mut env, expr) => env.eval(&expr),
+        }
+    }
+
+    /// Built-ins take unevaluated expressions, so to pass them values each one is
+    /// bound in a scratch scope under a name no program can spell.
+    fn scratch_args(&self, args: Vec<Rc<Value<'a>>>) -> (Env<'a>, Vec<ast::Expr>) {
+        let mut scratch = Env::with_outer(Rc::new(self.clone()));
+        let mut exprs = Vec::with_capacity(args.len());
+        for (i, arg) in args.into_iter().enumerate() {
+            let sym = ast::Symbol::from(format!(" arg{}", i));
+            scratch.insert(sym.clone(), arg);
+            exprs.push(ast::Expr::Symbol(sym));
+        }
+        (scratch, exprs)
+    }
 }
 
-fn func<'a>(_env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, String> {
-    if args.len() != 2 {
-        return Err("'fn' takes 2 arguments only".to_string());
+fn call_user_defined<'a>(
+    params: &[ast::Expr],
+    body: &[ast::Expr],
+    outer: &Rc<Env<'a>>,
+    args: Vec<Rc<Value<'a>>>,
+) -> Result<Tail<'a>, EvalError> {
+    // the call frame hangs off the environment the function was defined in,
+    // not the caller's, giving lexical scope
+    let mut new_env = Env::with_outer(Rc::clone(outer));
+    bind_params(&mut new_env, params, args)?;
+    eval_body(new_env, body)
+}
+
+/// Binds evaluated arguments to a parameter list, collecting any arguments
+/// beyond the fixed parameters into a list if there is a `&` rest parameter.
+fn bind_params<'a>(env: &mut Env<'a>, params: &[ast::Expr], args: Vec<Rc<Value<'a>>>) -> Result<(), EvalError> {
+    if args.iter().any(|arg| keyword_position(params, arg).is_some()) {
+        return bind_keyword_params(env, params, args);
+    }
+    let (fixed, rest) = match params.iter().position(is_rest_marker) {
+        Some(i) => (&params[..i], params.get(i + 1)),
+        None => (params, None),
+    };
+    if args.len() < fixed.len() || (rest.is_none() && args.len() > fixed.len()) {
+        let expected = match (fixed.len(), rest) {
+            (n, Some(_)) => format!("at least {}", plural(n, "argument")),
+            (0, None) => "no arguments".to_string(),
+            (n, None) => format!("{} only", plural(n, "argument")),
+        };
+        return Err(EvalError::arity(&user_defined_name(params), &expected));
+    }
+    let mut args = args.into_iter();
+    for (param, arg) in fixed.iter().zip(args.by_ref()) {
+        if let ast::Expr::Symbol(sym) = param {
+            env.insert(param_name(sym), arg);
+        }
+    }
+    if let Some(ast::Expr::Symbol(sym)) = rest {
+        env.insert(Rc::clone(sym), Rc::new(Value::List(args.collect())));
+    }
+    Ok(())
+}
+
+/// Binds arguments for a call using keywords, as in `(f 1 :z 3 :y 2)` for a
+/// function with parameters `(x :y :z)`: positional arguments come first, then
+/// keyword parameters may be given by name in any order.
+fn bind_keyword_params<'a>(env: &mut Env<'a>, params: &[ast::Expr], args: Vec<Rc<Value<'a>>>) -> Result<(), EvalError> {
+    let mut slots: Vec<Option<Rc<Value<'a>>>> = vec![None; params.len()];
+    let mut positional = 0;
+    let mut keywords = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match keyword_position(params, &arg) {
+            Some(i) => {
+                let value = args.next().ok_or_else(|| format!("Missing value for keyword '{}'", arg))?;
+                if slots[i].replace(value).is_some() {
+                    return Err(format!("Argument '{}' provided more than once", arg).into());
+                }
+                keywords = true;
+            },
+            None if keywords => return Err("Positional arguments must come before keyword arguments".into()),
+            None if positional < params.len() => {
+                slots[positional] = Some(arg);
+                positional += 1;
+            },
+            None => {
+                let expected = format!("at most {}", plural(params.len(), "argument"));
+                return Err(EvalError::arity(&user_defined_name(params), &expected));
+            },
+        }
+    }
+    for (param, slot) in params.iter().zip(slots) {
+        if let ast::Expr::Symbol(sym) = param {
+            let value = slot.ok_or_else(|| format!("Missing argument '{}'", sym))?;
+            env.insert(param_name(sym), value);
+        }
+    }
+    Ok(())
+}
+
+// user-defined functions have no name of their own, so errors show them as
+// they're displayed, such as `<fn (x y)>`
+fn user_defined_name(params: &[ast::Expr]) -> String {
+    let params: Vec<_> = params.iter().map(|param| param.to_string()).collect();
+    format!("<fn ({})>", params.join(" "))
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 { format!("1 {}", noun) } else { format!("{} {}s", n, noun) }
+}
+
+/// Where an argument names one of the keyword parameters, if it does.
+fn keyword_position(params: &[ast::Expr], arg: &Value) -> Option<usize> {
+    match arg {
+        Value::Symbol(key) if is_keyword(key) =>
+            params.iter().position(|param| matches!(param, ast::Expr::Symbol(sym) if sym == key)),
+        _ => None,
+    }
+}
+
+fn is_keyword(sym: &ast::Symbol) -> bool {
+    sym.len() > 1 && sym.starts_with(':')
+}
+
+/// The name a parameter is bound under: a keyword parameter `:x` binds `x`.
+fn param_name(sym: &ast::Symbol) -> ast::Symbol {
+    match is_keyword(sym) {
+        true => ast::Symbol::from(&sym[1..]),
+        false => Rc::clone(sym),
+    }
+}
+
+fn is_rest_marker(param: &ast::Expr) -> bool {
+    matches!(param, ast::Expr::Symbol(sym) if &**sym == "&")
+}
+
+/// Evaluates all but the last expression of a body, leaving the last in tail position.
+fn eval_body<'a>(mut env: Env<'a>, body: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    match body.split_last() {
+        Some((last, init)) => {
+            for expr in init {
+                env.eval(expr)?;
+            }
+            Ok(Tail::Eval(env, last.clone()))
+        },
+        None => Ok(Tail::Done(Rc::new(Value::Nil))),
+    }
+}
+
+fn func<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    lambda(env, args).map(Tail::Done)
+}
+
+fn lambda<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::arity("fn", "a parameter list and at least one body expression"));
     }
-    let params = match args.first().unwrap() {
+    let (params, body) = args.split_first().unwrap();
+    let params = match params {
         ast::Expr::List(list) => Ok(list),
-        _ => Err("..."),
+        _ => Err("First argument to 'fn' must be a list of parameters"),
     }?;
-    let body = args.get(1).unwrap();
+    if !params.iter().all(|param| matches!(param, ast::Expr::Symbol(_))) {
+        return Err("Parameters to 'fn' must be symbols".into());
+    }
+    if let Some(i) = params.iter().position(is_rest_marker) {
+        if params.len() != i + 2 || is_rest_marker(&params[i + 1]) {
+            return Err("'&' in a parameter list must be followed by exactly one symbol".into());
+        }
+        if params.iter().any(|param| matches!(param, ast::Expr::Symbol(sym) if is_keyword(sym))) {
+            return Err("Keyword parameters cannot be combined with '&'".into());
+        }
+    }
     Ok(Rc::new(Value::Func(Func::UserDefined { 
         params: params.to_vec(),
-        body: body.clone() })))
+        body: body.to_vec(),
+        env: Rc::new(env.clone()) })))
 }
 
-fn ifdef<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, String> {
-    if args.len() != 3 {
-        return Err("'if' takes 3 arguments".to_string());
+// the chosen branch is in tail position, so it's handed back to the loop in
+// `Env::eval` rather than evaluated here, and recursion through `if` runs in
+// constant native stack
+fn ifdef<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(EvalError::arity("if", "2 or 3 arguments"));
     }
     let cond = env.eval(args.first().unwrap())?;
     match cond.as_ref() {
         Value::Bool(b) => match b {
-            true => env.eval(args.get(1).unwrap()),
-            false => env.eval(args.get(2).unwrap()),
+            true => Ok(Tail::Eval(env.clone(), args.get(1).unwrap().clone())),
+            false => match args.get(2) {
+                Some(alt) => Ok(Tail::Eval(env.clone(), alt.clone())),
+                None => Ok(Tail::Done(Rc::new(Value::Nil))),
+            },
         },
-        _ => Err("Condition in 'if' must evaluate to a boolean value".to_string())
+        _ => Err("Condition in 'if' must evaluate to a boolean value".into())
     }
 }
 
-fn def<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, String> {
-    if args.len() != 2 {
-        return Err("'def' takes 2 arguments only".to_string());
-    }
-    let name = match args.first() {
-        Some(ast::Expr::Symbol(sym)) => Ok(sym),
-        _ => Err("First argument to 'def' must be a symbol"),
+/// `(try expr handler)` evaluates to `expr`, or to `handler` if evaluating
+/// `expr` fails; `(try expr msg handler)` also binds the error message to `msg`
+/// while evaluating the handler.
+fn try_catch<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    let (expr, sym, handler) = match args {
+        [expr, handler] => Ok((expr, None, handler)),
+        [expr, ast::Expr::Symbol(sym), handler] => Ok((expr, Some(sym), handler)),
+        [_, _, _] => Err("Error binding in 'try' must be a symbol".into()),
+        _ => Err(EvalError::arity("try", "2 or 3 arguments")),
     }?;
-    let value = env.eval(args.get(1).unwrap())?;
-    let sym = ast::Symbol::from(name);
-    env.insert(sym, value);
-    Ok(Rc::new(Value::Integer(0)))
+    // the guarded expression isn't in tail position, as its errors must be
+    // caught here
+    let msg = match env.eval(expr) {
+        Ok(value) => return Ok(Tail::Done(value)),
+        Err(msg) => msg,
+    };
+    match sym {
+        Some(sym) => {
+            let mut handler_env = Env::with_outer(Rc::new(env.clone()));
+            handler_env.insert(Rc::clone(sym), Rc::new(Value::Str(msg.to_string())));
+            Ok(Tail::Eval(handler_env, handler.clone()))
+        },
+        None => Ok(Tail::Eval(env.clone(), handler.clone())),
+    }
 }
 
-macro_rules! arithmetic_builtin {
-    ($name:ident, $op:tt) => {
-        fn $name<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, String> {
-            let (first, rest) = args.split_first().ok_or(concat!("Cannot apply '", stringify!($op), "' to zero arguments"))?;
-            let first = env.eval(first)?;
-            match first.as_ref() {
-                Value::Integer(i) => {
-                    let mut res: i64 = *i;
-                    for item in rest {
-                        let value = env.eval(item)?;
-                        let value = match value.as_ref() {
-                            Value::Integer(j) => Ok(j),
-                            _ => Err(format!(concat!("Non-integer '{}' found in integer ", stringify!($name)), value))
-                        }?;
-                        res = res $op value;
-                    }
-                    Ok(Rc::new(Value::Integer(res)))
-                },
-                Value::Float(f) => {
-                    let mut res: f64 = *f;
-                    for item in rest {
-                        let value = env.eval(item)?;
-                        let value = match value.as_ref() {
-                            Value::Float(g) => Ok(g),
-                            _ => Err(format!(concat!("Non-float '{}' found in float ", stringify!($name)), value))
-                        }?;
-                        res = res $op value;
-                    }
-                    Ok(Rc::new(Value::Float(res)))
-                },
-                _ => Err("Must apply '+' to numeric types".to_string())
-            }
+// the value (shown as by `print`) becomes the message of an ordinary evaluation
+// error, so it can be caught by `try`
+fn error<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("error", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    Err(EvalError::UserError(value.to_string()))
+}
+
+/// Fails unless the condition evaluates to true, with a message naming the
+/// condition or given by the optional second argument.
+fn assert<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 && args.len() != 2 {
+        return Err(EvalError::arity("assert", "1 or 2 arguments"));
+    }
+    let cond = args.first().unwrap();
+    let value = env.eval(cond)?;
+    if let Value::Bool(true) = value.as_ref() {
+        return Ok(Rc::new(Value::Nil));
+    }
+    match args.get(1) {
+        Some(msg) => Err(EvalError::UserError(env.eval(msg)?.to_string())),
+        None => Err(EvalError::UserError(format!("Assertion failed: {}", cond))),
+    }
+}
+
+fn when<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    one_armed(env, args, "when", true)
+}
+
+fn unless<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    one_armed(env, args, "unless", false)
+}
+
+/// Evaluates the body after the condition, as with `begin`, only if the
+/// condition evaluates to `expected`; otherwise gives nil.
+fn one_armed<'a>(env: &mut Env<'a>, args: &[ast::Expr], name: &str, expected: bool) -> Result<Tail<'a>, EvalError> {
+    let (cond, body) = args.split_first().ok_or_else(|| EvalError::arity(name, "a condition and a body"))?;
+    let value = env.eval(cond)?;
+    match value.as_ref() {
+        Value::Bool(b) if *b == expected => eval_body(env.clone(), body),
+        Value::Bool(_) => Ok(Tail::Done(Rc::new(Value::Nil))),
+        _ => Err(format!("Condition in '{}' must evaluate to a boolean value", name).into()),
+    }
+}
+
+fn cond<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    for clause in args {
+        let (test, body) = match clause {
+            ast::Expr::List(list) if !list.is_empty() => Ok(list.split_first().unwrap()),
+            _ => Err("Clauses in 'cond' must be of the form (test body...)"),
+        }?;
+        let matched = match test {
+            ast::Expr::Symbol(sym) if &**sym == "else" => true,
+            _ => {
+                let value = env.eval(test)?;
+                match value.as_ref() {
+                    Value::Bool(b) => *b,
+                    _ => return Err("Test in 'cond' must evaluate to a boolean value".into()),
+                }
+            },
+        };
+        if matched {
+            return eval_body(env.clone(), body);
         }
-    };
+    }
+    Ok(Tail::Done(Rc::new(Value::Nil)))
 }
 
-arithmetic_builtin!(addition, +);
-arithmetic_builtin!(subtraction, -);
-arithmetic_builtin!(multiplication, *);
-arithmetic_builtin!(division, /);
+// the key is evaluated once; each datum is taken literally, as if quoted, and
+// compared with it as by `=`, so must be of the same type
+fn case<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    let (key, clauses) = args.split_first().ok_or_else(|| EvalError::arity("case", "a key and clauses"))?;
+    let key = env.eval(key)?;
+    if let Value::Func(_) = key.as_ref() {
+        return Err("Cannot apply 'case' to functions".into());
+    }
+    for clause in clauses {
+        let (datum, body) = match clause {
+            ast::Expr::List(list) if !list.is_empty() => Ok(list.split_first().unwrap()),
+            _ => Err("Clauses in 'case' must be of the form (datum body...)"),
+        }?;
+        let matched = match datum {
+            ast::Expr::Symbol(sym) if &**sym == "else" => true,
+            _ => {
+                let value = quoted(datum);
+                same_kind_equal(&key, &value).ok_or_else(|| EvalError::type_mismatch("case", key.type_name(), &value))?
+            },
+        };
+        if matched {
+            return eval_body(env.clone(), body);
+        }
+    }
+    Ok(Tail::Done(Rc::new(Value::Nil)))
+}
 
-fn equals<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, String> {
-    if args.len() < 2 {
-        return Err("Cannot apply '=' to fewer than 2 arguments".to_string());
+fn whiledef<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    let (cond, body) = args.split_first().ok_or_else(|| EvalError::arity("while", "a condition and a body"))?;
+    loop {
+        let value = env.eval(cond)?;
+        match value.as_ref() {
+            Value::Bool(true) => {},
+            Value::Bool(false) => return Ok(Tail::Done(Rc::new(Value::Nil))),
+            _ => return Err("Condition in 'while' must evaluate to a boolean value".into()),
+        }
+        for expr in body {
+            env.eval(expr)?;
+        }
     }
-    let (first, rest) = args.split_first().unwrap();
-    let first = env.eval(first)?;
-    match first.as_ref() {
-        Value::Bool(b) => {
-            let mut res = true;
-            for item in rest {
-                let value = env.eval(item)?;
-                res = match value.as_ref() {
-                    Value::Bool(c) => Ok(res && (b == c)),
-                    _ => Err(format!("Non-boolean '{}' found in boolean equals", value)),
-                }?;
-                if !res { break; }
-            }
-            Ok(Rc::new(Value::Bool(res)))
-        },
-        Value::Integer(i) => {
-            let mut res = true;
-            for item in rest {
-                let value = env.eval(item)?;
-                res = match value.as_ref() {
-                    Value::Integer(j) => Ok(res && (i == j)),
-                    _ => Err(format!("Non-integer '{}' found in integer equals", value)),
-                }?;
-                if !res { break; }
-            }
-            Ok(Rc::new(Value::Bool(res)))
-        },
-        Value::Float(f) => {
-            let mut res = true;
-            for item in rest {
-                let value = env.eval(item)?;
-                res = match value.as_ref() {
-                    Value::Float(g) => Ok(res && (f == g)),
-                    _ => Err(format!("Non-float '{}' found in float equals", value)),
-                }?;
-                if !res { break; }
-            }
-            Ok(Rc::new(Value::Bool(res)))
+}
+
+// each iteration gets a fresh scope, so closures made in the body capture that
+// iteration's value of the loop variable
+fn dotimes<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    let (spec, body) = args.split_first().ok_or_else(|| EvalError::arity("dotimes", "a (symbol count) pair and a body"))?;
+    let (sym, count) = match spec {
+        ast::Expr::List(pair) => match pair.as_ref() {
+            [ast::Expr::Symbol(sym), count] => Ok((sym, count)),
+            _ => Err("First argument to 'dotimes' must be of the form (symbol count)"),
         },
-        _ => Err("Must apply '=' to numeric or boolean types".to_string()),
+        _ => Err("First argument to 'dotimes' must be of the form (symbol count)"),
+    }?;
+    let count = env.eval(count)?;
+    let count = match count.as_ref() {
+        Value::Integer(n) if *n >= 0 => Ok(*n),
+        _ => Err(format!("Count '{}' in 'dotimes' must be a non-negative integer", count)),
+    }?;
+    for i in 0..count {
+        let mut loop_env = Env::with_outer(Rc::new(env.clone()));
+        loop_env.insert(Rc::clone(sym), Rc::new(Value::Integer(i)));
+        for expr in body {
+            loop_env.eval(expr)?;
+        }
     }
+    Ok(Tail::Done(Rc::new(Value::Nil)))
 }
 
-fn less_than<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, String> {
-    if args.len() < 2 {
-        return Err("Cannot apply '<' to fewer than 2 arguments".to_string());
+fn begin<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    eval_body(env.clone(), args)
+}
+
+fn letdef<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    if let Some(ast::Expr::Symbol(name)) = args.first() {
+        return named_let(env, name, &args[1..]);
     }
-    let (first, _rest) = args.split_first().unwrap();
-    let first = env.eval(first)?;
-    match first.as_ref() {
-        Value::Integer(i) => {
-            let second = args.get(1).unwrap();
-            let second = env.eval(second)?;
-            match second.as_ref() {
-                Value::Integer(j) => Ok(Rc::new(Value::Bool(i < j))),
-                _ => Err(format!("Cannot compare integer '{}' with non-integer '{}'", first, second)),
-            }
-        },
-        Value::Float(f) => {
-            let second = args.get(1).unwrap();
-            let second = env.eval(second)?;
-            match second.as_ref() {
-                Value::Float(g) => Ok(Rc::new(Value::Bool(f < g))),
-                _ => Err(format!("Cannot compare float '{}' with non-float '{}'", first, second)),
-            }
-        },
-        _ => Err(format!("Cannot use value '{}' for comparison", first)),
+    let (bindings, body) = let_parts(args, "let")?;
+    // bindings are parallel: every value is evaluated in the enclosing scope
+    // before any name is bound, so none can refer to another
+    let mut values = Vec::with_capacity(bindings.len());
+    for binding in bindings {
+        let (sym, expr) = let_binding(binding)?;
+        values.push((sym, env.eval(expr)?));
     }
+    let mut new_env = Env::with_outer(Rc::new(env.clone()));
+    for (sym, value) in values {
+        new_env.insert(Rc::clone(sym), value);
+    }
+    Ok(Tail::Eval(new_env, body.clone()))
 }
 
-impl Display for Value<'_> {
-    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
-        match self {
-            Value::Bool(b) => write!(fmt, "{}", b),
-            Value::Integer(i) => write!(fmt, "{}", i),
-            Value::Float(f) => write!(fmt, "{}", f),
-            Value::List(list) => {
-                write!(fmt, "(")?;
-                for item in list.iter() {
-                    write!(fmt, "{} ", item)?;
-                }
-                write!(fmt, ")")
-            },
-            Value::Func(func) => match func {
-                Func::BuiltIn { name, .. } =>
-                    write!(fmt, "<built-in function '{}'>", name),
-                Func::UserDefined { .. } =>
-                    write!(fmt, "function")
-            }
-        }
+fn let_star<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    let (bindings, body) = let_parts(args, "let*")?;
+    // bindings are sequential: each value is evaluated in the new scope, so it
+    // can refer to the names bound before it
+    let mut new_env = Env::with_outer(Rc::new(env.clone()));
+    for binding in bindings {
+        let (sym, expr) = let_binding(binding)?;
+        let value = new_env.eval(expr)?;
+        new_env.insert(Rc::clone(sym), value);
     }
+    Ok(Tail::Eval(new_env, body.clone()))
 }
 
-impl Display for Func<'_> {
-    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
-        write!(fmt, "func")
+/// Splits the arguments to `let` or `let*` into the bindings and the body.
+fn let_parts<'e>(args: &'e [ast::Expr], name: &str) -> Result<(&'e [ast::Expr], &'e ast::Expr), EvalError> {
+    match args {
+        [ast::Expr::List(bindings), body] => Ok((bindings, body)),
+        [_, _] => Err(format!("First argument to '{}' must be a list of bindings", name).into()),
+        _ => Err(EvalError::arity(name, "2 arguments only")),
+    }
+}
+
+/// `(let name ((sym init)...) body)` binds `name` to a function of the bound
+/// symbols with the given body, visible only within that body, and calls it
+/// with the initial values, so the body can loop by calling `name` again.
+fn named_let<'a>(env: &mut Env<'a>, name: &ast::Symbol, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    if args.len() != 2 {
+        return Err("Named 'let' takes a name, a list of bindings and a body".into());
+    }
+    let bindings = match args.first().unwrap() {
+        ast::Expr::List(list) => Ok(list),
+        _ => Err("Second argument to named 'let' must be a list of bindings"),
+    }?;
+    let mut params = Vec::with_capacity(bindings.len());
+    let mut inits = Vec::with_capacity(bindings.len());
+    for binding in bindings.iter() {
+        let (sym, expr) = let_binding(binding)?;
+        params.push(ast::Expr::Symbol(Rc::clone(sym)));
+        // the initial values are evaluated outside the loop's scope
+        inits.push(env.eval(expr)?);
+    }
+    let mut loop_env = Env::with_outer(Rc::new(env.clone()));
+    let body = vec![args.get(1).unwrap().clone()];
+    let outer = Rc::new(loop_env.clone());
+    loop_env.insert(Rc::clone(name), Rc::new(Value::Func(Func::UserDefined {
+        params: params.clone(),
+        body: body.clone(),
+        env: Rc::clone(&outer),
+    })));
+    call_user_defined(&params, &body, &outer, inits)
+}
+
+fn let_binding(binding: &ast::Expr) -> Result<(&ast::Symbol, &ast::Expr), &'static str> {
+    match binding {
+        ast::Expr::List(pair) => match pair.as_ref() {
+            [ast::Expr::Symbol(sym), expr] => Ok((sym, expr)),
+            _ => Err("Bindings in 'let' must be of the form (symbol value)"),
+        },
+        _ => Err("Bindings in 'let' must be of the form (symbol value)"),
     }
 }
 
+fn def<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("def", "2 arguments only"));
+    }
+    let name = match args.first() {
+        Some(ast::Expr::Symbol(sym)) => Ok(sym),
+        _ => Err("First argument to 'def' must be a symbol"),
+    }?;
+    let value = env.eval(args.get(1).unwrap())?;
+    let sym = Rc::clone(name);
+    env.insert(sym, value);
+    Ok(Tail::Done(Rc::new(Value::Nil)))
+}
+
+/// `(defn name (params...) body...)` is shorthand for
+/// `(def name (fn (params...) body...))`.
+fn defn<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    let (name, rest) = match args.split_first() {
+        Some((ast::Expr::Symbol(sym), rest)) => Ok((sym, rest)),
+        _ => Err("First argument to 'defn' must be a symbol"),
+    }?;
+    let value = lambda(env, rest)?;
+    env.insert(Rc::clone(name), value);
+    Ok(Tail::Done(Rc::new(Value::Nil)))
+}
+
+fn set<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("set!", "2 arguments only"));
+    }
+    let name = match args.first() {
+        Some(ast::Expr::Symbol(sym)) => Ok(sym),
+        _ => Err("First argument to 'set!' must be a symbol"),
+    }?;
+    let value = env.eval(args.get(1).unwrap())?;
+    if !env.set(name, value) {
+        return Err(format!("Cannot set undefined symbol '{}'", name).into());
+    }
+    Ok(Tail::Done(Rc::new(Value::Nil)))
+}
+
+/// Arithmetic operands, all promoted to floats if any one of them is a float,
+/// or else to fractions if any one of them is a rational.
+enum Numbers {
+    Integers(Vec<i64>),
+    Rationals(Vec<(i64, i64)>),
+    Floats(Vec<f64>),
+}
+
+fn numeric_args<'a>(env: &mut Env<'a>, args: &[ast::Expr], name: &str) -> Result<Numbers, EvalError> {
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        values.push(env.eval(arg)?);
+    }
+    let (mut floats, mut rationals) = (false, false);
+    for value in values.iter() {
+        match value.as_ref() {
+            Value::Integer(_) => {},
+            Value::Rational { .. } => rationals = true,
+            Value::Float(_) => floats = true,
+            _ => return Err(EvalError::type_mismatch(name, "number", value)),
+        }
+    }
+    let numbers = if floats {
+        Numbers::Floats(values.iter().map(|value| match value.as_ref() {
+            Value::Integer(i) => *i as f64,
+            Value::Rational { num, den } => *num as f64 / *den as f64,
+            Value::Float(f) => *f,
+            _ => unreachable!(),
+        }).collect())
+    } else if rationals {
+        Numbers::Rationals(values.iter().map(|value| fraction(value).unwrap()).collect())
+    } else {
+        Numbers::Integers(values.iter().map(|value| match value.as_ref() {
+            Value::Integer(i) => *i,
+            _ => unreachable!(),
+        }).collect())
+    };
+    Ok(numbers)
+}
+
+/// Combines two fractions `a/b` and `c/d` into an unreduced `(num, den)`.
+type RationalOp = fn(i128, i128, i128, i128) -> Result<(i128, i128), EvalError>;
+
+fn arithmetic<'a>(
+    env: &mut Env<'a>,
+    args: &[ast::Expr],
+    name: &str,
+    int_op: fn(i64, i64) -> Result<i64, EvalError>,
+    rational_op: RationalOp,
+    float_op: fn(f64, f64) -> f64,
+) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.is_empty() {
+        return Err(format!("Cannot apply '{}' to zero arguments", name).into());
+    }
+    match unary(numeric_args(env, args, name)?, name) {
+        Numbers::Integers(ints) => {
+            let (first, rest) = ints.split_first().unwrap();
+            let mut res = *first;
+            for value in rest {
+                res = int_op(res, *value)?;
+            }
+            Ok(Rc::new(Value::Integer(res)))
+        },
+        Numbers::Rationals(fracs) => {
+            let (first, rest) = fracs.split_first().unwrap();
+            let mut res = *first;
+            for (num, den) in rest {
+                let (num, den) = rational_op(res.0.into(), res.1.into(), (*num).into(), (*den).into())?;
+                res = ast::reduce_fraction(num, den).ok_or(EvalError::IntegerOverflow)?;
+            }
+            Ok(Rc::new(rational(res)))
+        },
+        Numbers::Floats(floats) => {
+            let (first, rest) = floats.split_first().unwrap();
+            let res = rest.iter().fold(*first, |acc, value| float_op(acc, *value));
+            Ok(Rc::new(Value::Float(res)))
+        },
+    }
+}
+
+/// Rewrites a single operand of `-` or `/` as `(- 0 x)` or `(/ 1 x)`, so that
+/// they negate and take the reciprocal. The reciprocal of an integer is exact.
+fn unary(numbers: Numbers, name: &str) -> Numbers {
+    match (numbers, name) {
+        (Numbers::Integers(ints), "-") if ints.len() == 1 => Numbers::Integers(vec![0, ints[0]]),
+        (Numbers::Integers(ints), "/") if ints.len() == 1 => Numbers::Rationals(vec![(1, 1), (ints[0], 1)]),
+        (Numbers::Rationals(fracs), "-") if fracs.len() == 1 => Numbers::Rationals(vec![(0, 1), fracs[0]]),
+        (Numbers::Rationals(fracs), "/") if fracs.len() == 1 => Numbers::Rationals(vec![(1, 1), fracs[0]]),
+        (Numbers::Floats(floats), "-") if floats.len() == 1 => Numbers::Floats(vec![0.0, floats[0]]),
+        (Numbers::Floats(floats), "/") if floats.len() == 1 => Numbers::Floats(vec![1.0, floats[0]]),
+        (numbers, _) => numbers,
+    }
+}
+
+macro_rules! arithmetic_builtin {
+    ($name:ident, $op:tt, $int_op:expr, $rational_op:expr) => {
+        fn $name<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+            arithmetic(env, args, stringify!($op), $int_op, $rational_op, |a, b| a $op b)
+        }
+    };
+}
+
+// integer arithmetic is always checked, so overflow is an error in both debug
+// and release builds rather than a panic or a silently wrapped result. Fractions
+// (a/b and c/d) are combined in 128 bits, so only the reduced result can overflow
+arithmetic_builtin!(addition, +, |a, b| no_overflow(a.checked_add(b)), |a, b, c, d| Ok((a * d + c * b, b * d)));
+arithmetic_builtin!(subtraction, -, |a, b| no_overflow(a.checked_sub(b)), |a, b, c, d| Ok((a * d - c * b, b * d)));
+arithmetic_builtin!(multiplication, *, |a, b| no_overflow(a.checked_mul(b)), |a, b, c, d| Ok((a * c, b * d)));
+// integer division truncates, even though rationals exist; float division by
+// zero is left to IEEE semantics and yields `inf` or `NaN`
+arithmetic_builtin!(division, /, |a, b| {
+    nonzero_divisor(b)?;
+    no_overflow(a.checked_div(b))
+}, |a, b, c, d| {
+    if c == 0 {
+        return Err(EvalError::DivisionByZero);
+    }
+    Ok((a * d, b * c))
+});
+
+/// The value of a reduced fraction: an integer if it's a whole number.
+fn rational<'a>((num, den): (i64, i64)) -> Value<'a> {
+    match den {
+        1 => Value::Integer(num),
+        _ => Value::Rational { num, den },
+    }
+}
+
+/// An integer or rational as a fraction.
+fn fraction(value: &Value) -> Option<(i64, i64)> {
+    match value {
+        Value::Integer(i) => Some((*i, 1)),
+        Value::Rational { num, den } => Some((*num, *den)),
+        _ => None,
+    }
+}
+
+/// Whether two values of the same kind are equal, or `None` if their kinds
+/// differ. Integers and rationals are both exact numbers, so they compare.
+fn same_kind_equal<'a>(a: &Value<'a>, b: &Value<'a>) -> Option<bool> {
+    match (fraction(a), fraction(b)) {
+        (Some(x), Some(y)) => Some(compare_fractions(x, y) == Ordering::Equal),
+        _ if mem::discriminant(a) == mem::discriminant(b) => Some(a == b),
+        _ => None,
+    }
+}
+
+fn compare_fractions((a, b): (i64, i64), (c, d): (i64, i64)) -> Ordering {
+    // denominators are positive, so cross-multiplying preserves the order
+    (i128::from(a) * i128::from(d)).cmp(&(i128::from(c) * i128::from(b)))
+}
+
+fn no_overflow(value: Option<i64>) -> Result<i64, EvalError> {
+    value.ok_or(EvalError::IntegerOverflow)
+}
+
+fn nonzero_divisor(value: i64) -> Result<(), EvalError> {
+    match value {
+        0 => Err(EvalError::DivisionByZero),
+        _ => Ok(()),
+    }
+}
+
+// the remainder takes the sign of the dividend, matching Rust's `%`
+fn modulo<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    let (first, rest) = args.split_first().ok_or_else(|| EvalError::arity("mod", "at least 1 argument"))?;
+    let first = env.eval(first)?;
+    match first.as_ref() {
+        Value::Integer(i) => {
+            let mut res: i64 = *i;
+            for item in rest {
+                let value = env.eval(item)?;
+                let value = match value.as_ref() {
+                    Value::Integer(j) => Ok(*j),
+                    _ => Err(EvalError::type_mismatch("mod", "integer", &value)),
+                }?;
+                nonzero_divisor(value)?;
+                res = res.wrapping_rem(value);
+            }
+            Ok(Rc::new(Value::Integer(res)))
+        },
+        _ => Err(EvalError::type_mismatch("mod", "integer", &first)),
+    }
+}
+
+/// Evaluates an argument that must be an integer.
+fn integer_arg(env: &mut Env, arg: &ast::Expr, name: &str) -> Result<i64, EvalError> {
+    let value = env.eval(arg)?;
+    match value.as_ref() {
+        Value::Integer(i) => Ok(*i),
+        _ => Err(EvalError::type_mismatch(name, "integer", &value)),
+    }
+}
+
+macro_rules! bitwise_builtin {
+    ($name:ident, $tag:expr, $op:tt) => {
+        fn $name<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+            let (first, rest) = args.split_first()
+                .ok_or(concat!("Cannot apply '", $tag, "' to zero arguments"))?;
+            let mut res = integer_arg(env, first, $tag)?;
+            for item in rest {
+                res $op integer_arg(env, item, $tag)?;
+            }
+            Ok(Rc::new(Value::Integer(res)))
+        }
+    };
+}
+
+bitwise_builtin!(bit_and, "bit-and", &=);
+bitwise_builtin!(bit_or, "bit-or", |=);
+bitwise_builtin!(bit_xor, "bit-xor", ^=);
+
+fn bit_not<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("bit-not", "1 argument only"));
+    }
+    Ok(Rc::new(Value::Integer(!integer_arg(env, args.first().unwrap(), "bit-not")?)))
+}
+
+// right shifts are arithmetic, keeping the sign, and shifting left is an
+// overflow as soon as a bit other than the sign is shifted out
+fn bit_shift<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("bit-shift", "2 arguments only"));
+    }
+    let value = integer_arg(env, args.first().unwrap(), "bit-shift")?;
+    let count = integer_arg(env, args.get(1).unwrap(), "bit-shift")?;
+    let res = match count {
+        // there are no bits to lose, however far they're shifted
+        _ if value == 0 => Some(0),
+        0.. => u32::try_from(count).ok()
+            .and_then(|count| value.checked_shl(count))
+            .filter(|res| res >> count == value),
+        // shifting right by 64 or more leaves only copies of the sign bit
+        _ => Some(value >> count.unsigned_abs().min(63)),
+    };
+    Ok(Rc::new(Value::Integer(no_overflow(res)?)))
+}
+
+macro_rules! extremum_builtin {
+    ($name:ident, $pick:ident, $ord:ident) => {
+        // mixed numbers are promoted as in arithmetic
+        fn $name<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+            if args.is_empty() {
+                return Err(concat!("Cannot apply '", stringify!($name), "' to zero arguments").into());
+            }
+            match numeric_args(env, args, stringify!($name))? {
+                Numbers::Integers(ints) =>
+                    Ok(Rc::new(Value::Integer(ints.into_iter().reduce(i64::$pick).unwrap()))),
+                Numbers::Rationals(fracs) => {
+                    let res = fracs.into_iter().reduce(|acc, frac| {
+                        if compare_fractions(frac, acc) == Ordering::$ord { frac } else { acc }
+                    });
+                    Ok(Rc::new(rational(res.unwrap())))
+                },
+                Numbers::Floats(floats) =>
+                    Ok(Rc::new(Value::Float(floats.into_iter().reduce(f64::$pick).unwrap()))),
+            }
+        }
+    };
+}
+
+extremum_builtin!(min, min, Less);
+extremum_builtin!(max, max, Greater);
+
+fn abs<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("abs", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Integer(i) => Ok(Rc::new(Value::Integer(no_overflow(i.checked_abs())?))),
+        Value::Rational { num, den } =>
+            Ok(Rc::new(Value::Rational { num: no_overflow(num.checked_abs())?, den: *den })),
+        Value::Float(f) => Ok(Rc::new(Value::Float(f.abs()))),
+        _ => Err(EvalError::type_mismatch("abs", "number", &value)),
+    }
+}
+
+/// Rounds a float (or a rational, as a float) to a whole number, which is
+/// still a float; integers are already whole and are returned as they are.
+fn rounding<'a>(
+    env: &mut Env<'a>,
+    args: &[ast::Expr],
+    name: &str,
+    op: fn(f64) -> f64,
+) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity(name, "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Integer(_) => Ok(value),
+        Value::Rational { num, den } => Ok(Rc::new(Value::Float(op(*num as f64 / *den as f64)))),
+        Value::Float(f) => Ok(Rc::new(Value::Float(op(*f)))),
+        _ => Err(EvalError::type_mismatch(name, "number", &value)),
+    }
+}
+
+fn floor<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    rounding(env, args, "floor", f64::floor)
+}
+
+fn ceil<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    rounding(env, args, "ceil", f64::ceil)
+}
+
+// halfway cases round away from zero, so `(round 2.5)` is 3
+fn round<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    rounding(env, args, "round", f64::round)
+}
+
+// truncates rather than rounds, as a cast does, so `(to-int -3.9)` is -3; use
+// `floor`, `ceil` or `round` first for anything else
+fn to_int<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("to-int", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Integer(_) => Ok(value),
+        Value::Rational { num, den } => Ok(Rc::new(Value::Integer(num / den))),
+        // `i64::MIN` is a power of two, so exactly representable as a float
+        Value::Float(f) if f.trunc() >= i64::MIN as f64 && f.trunc() < -(i64::MIN as f64) =>
+            Ok(Rc::new(Value::Integer(f.trunc() as i64))),
+        Value::Float(_) => Err(format!("Float '{}' is out of range for 'to-int'", value).into()),
+        _ => Err(EvalError::type_mismatch("to-int", "number", &value)),
+    }
+}
+
+fn to_float<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("to-float", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Integer(i) => Ok(Rc::new(Value::Float(*i as f64))),
+        Value::Rational { num, den } => Ok(Rc::new(Value::Float(*num as f64 / *den as f64))),
+        Value::Float(_) => Ok(value),
+        _ => Err(EvalError::type_mismatch("to-float", "number", &value)),
+    }
+}
+
+// arguments must all be of the same type as the first, but lists may hold
+// elements of differing types (which just compare unequal)
+fn equals<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::arity("=", "at least 2 arguments"));
+    }
+    let (first, rest) = args.split_first().unwrap();
+    let first = env.eval(first)?;
+    if let Value::Func(_) = first.as_ref() {
+        return Err("Cannot apply '=' to functions".into());
+    }
+    let mut res = true;
+    for item in rest {
+        let value = env.eval(item)?;
+        res = res && same_kind_equal(&first, &value).ok_or_else(|| EvalError::type_mismatch("=", first.type_name(), &value))?;
+        if !res { break; }
+    }
+    Ok(Rc::new(Value::Bool(res)))
+}
+
+fn not_equals<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("!=", "2 arguments only"));
+    }
+    match equals(env, args)?.as_ref() {
+        Value::Bool(b) => Ok(Rc::new(Value::Bool(!b))),
+        _ => unreachable!(),
+    }
+}
+
+macro_rules! comparison_builtin {
+    ($name:ident, $op:tt) => {
+        // the relation must hold between every adjacent pair of arguments; evaluation
+        // is left to right and stops at the first pair for which it doesn't
+        fn $name<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+            if args.len() < 2 {
+                return Err(EvalError::arity(stringify!($op), "at least 2 arguments"));
+            }
+            let (first, rest) = args.split_first().unwrap();
+            let mut prev = env.eval(first)?;
+            for item in rest {
+                let next = env.eval(item)?;
+                let holds = match (prev.as_ref(), next.as_ref()) {
+                    (Value::Integer(i), Value::Integer(j)) => i $op j,
+                    (Value::Float(f), Value::Float(g)) => f $op g,
+                    // integers and rationals are both exact, so compare freely
+                    (Value::Integer(_) | Value::Rational { .. }, Value::Integer(_) | Value::Rational { .. }) =>
+                        compare_fractions(fraction(&prev).unwrap(), fraction(&next).unwrap()) $op Ordering::Equal,
+                    (Value::Integer(_), _) => return Err(EvalError::type_mismatch(stringify!($op), "integer", &next)),
+                    (Value::Rational { .. }, _) => return Err(EvalError::type_mismatch(stringify!($op), "rational", &next)),
+                    (Value::Float(_), _) => return Err(EvalError::type_mismatch(stringify!($op), "float", &next)),
+                    _ => return Err(EvalError::type_mismatch(stringify!($op), "number", &prev)),
+                };
+                if !holds {
+                    return Ok(Rc::new(Value::Bool(false)));
+                }
+                prev = next;
+            }
+            Ok(Rc::new(Value::Bool(true)))
+        }
+    };
+}
+
+comparison_builtin!(less_than, <);
+comparison_builtin!(greater_than, >);
+comparison_builtin!(less_than_or_equal, <=);
+comparison_builtin!(greater_than_or_equal, >=);
+
+// `and` and `or` short-circuit: arguments are evaluated left to right only until
+// the result is decided, so side effects in the remaining arguments never run
+fn and<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    for arg in args {
+        let value = env.eval(arg)?;
+        match value.as_ref() {
+            Value::Bool(true) => {},
+            Value::Bool(false) => return Ok(value),
+            _ => return Err(EvalError::type_mismatch("and", "bool", &value)),
+        }
+    }
+    Ok(Rc::new(Value::Bool(true)))
+}
+
+fn or<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    for arg in args {
+        let value = env.eval(arg)?;
+        match value.as_ref() {
+            Value::Bool(true) => return Ok(value),
+            Value::Bool(false) => {},
+            _ => return Err(EvalError::type_mismatch("or", "bool", &value)),
+        }
+    }
+    Ok(Rc::new(Value::Bool(false)))
+}
+
+fn not<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("not", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Bool(b) => Ok(Rc::new(Value::Bool(!b))),
+        _ => Err(EvalError::type_mismatch("not", "boolean", &value)),
+    }
+}
+
+fn help<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("help", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Func(func) => Ok(Rc::new(Value::Str(describe(func)))),
+        _ => Err(format!("Cannot get help for non-function '{}'", value).into()),
+    }
+}
+
+fn describe(func: &Func) -> String {
+    match func {
+        Func::BuiltIn { name, doc, .. } | Func::SpecialForm { name, doc, .. } =>
+            format!("built-in function '{}': {}", name, doc),
+        Func::UserDefined { .. } => format!("user-defined function {}", func),
+        Func::Memoized { func, .. } => match func.as_ref() {
+            Value::Func(func) => format!("memoized {}", describe(func)),
+            _ => unreachable!(),
+        },
+    }
+}
+
+fn type_of<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("type", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    Ok(Rc::new(Value::Str(value.type_name().to_string())))
+}
+
+macro_rules! predicate_builtin {
+    ($name:ident, $tag:expr, $pattern:pat) => {
+        fn $name<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+            if args.len() != 1 {
+                return Err(EvalError::arity($tag, "1 argument only"));
+            }
+            let value = env.eval(args.first().unwrap())?;
+            Ok(Rc::new(Value::Bool(matches!(value.as_ref(), $pattern))))
+        }
+    };
+}
+
+predicate_builtin!(is_nil, "nil?", Value::Nil);
+predicate_builtin!(is_bool, "bool?", Value::Bool(_));
+predicate_builtin!(is_number, "number?", Value::Integer(_) | Value::Rational { .. } | Value::Float(_));
+predicate_builtin!(is_list, "list?", Value::List(_));
+predicate_builtin!(is_func, "fn?", Value::Func(_));
+
+fn print<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    write_values(env, args, "")
+}
+
+fn println<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    write_values(env, args, "\n")
+}
+
+fn pprint<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("pprint", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    let mut out = env.output.borrow_mut();
+    writeln!(out, "{}", value.pretty(0, env.float_precision))
+        .and_then(|_| out.flush())
+        .map_err(|err| format!("Cannot write output: {}", err))?;
+    Ok(Rc::new(Value::Nil))
+}
+
+/// Evaluates an expression, reporting how long that took on stderr.
+fn time<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("time", "1 argument only"));
+    }
+    let start = Instant::now();
+    let value = env.eval(args.first().unwrap())?;
+    eprintln!("Elapsed time: {:?}", start.elapsed());
+    Ok(value)
+}
+
+/// Writes the arguments to the environment's output separated by spaces,
+/// followed by `end`.
+fn write_values<'a>(env: &mut Env<'a>, args: &[ast::Expr], end: &str) -> Result<Rc<Value<'a>>, EvalError> {
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        let value = env.eval(arg)?;
+        values.push(env.display(&value));
+    }
+    let mut out = env.output.borrow_mut();
+    write!(out, "{}{}", values.join(" "), end)
+        .and_then(|_| out.flush())
+        .map_err(|err| format!("Cannot write output: {}", err))?;
+    Ok(Rc::new(Value::Nil))
+}
+
+/// Reads one line from stdin, blocking until it's available, and returns it
+/// without its line ending. Returns nil once stdin is exhausted.
+fn read_line<'a>(_env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if !args.is_empty() {
+        return Err(EvalError::arity("read-line", "no arguments"));
+    }
+    let mut line = String::new();
+    let read = io::stdin().lock().read_line(&mut line)
+        .map_err(|err| format!("Cannot read from stdin: {}", err))?;
+    if read == 0 {
+        return Ok(Rc::new(Value::Nil));
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Rc::new(Value::Str(line)))
+}
+
+fn read_file<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("read-file", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Str(path) => fs::read_to_string(path)
+            .map(|contents| Rc::new(Value::Str(contents)))
+            .map_err(|err| format!("Cannot read '{}': {}", path, err).into()),
+        _ => Err(EvalError::type_mismatch("read-file", "string", &value)),
+    }
+}
+
+/// Evaluates each expression in a source file in the calling scope, returning
+/// the value of the last one.
+fn load<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("load", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    let path = match value.as_ref() {
+        Value::Str(path) => Ok(path),
+        _ => Err(EvalError::type_mismatch("load", "string", &value)),
+    }?;
+    env.load_file(path)
+}
+
+fn quote<'a>(_env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("quote", "1 argument only"));
+    }
+    Ok(Tail::Done(quoted(args.first().unwrap())))
+}
+
+/// Converts an expression into the value it denotes as data, without evaluating it.
+fn quoted<'a>(expr: &ast::Expr) -> Rc<Value<'a>> {
+    Rc::new(match expr {
+        ast::Expr::Nil => Value::Nil,
+        ast::Expr::Bool(b) => Value::Bool(*b),
+        ast::Expr::Integer(i) => Value::Integer(*i),
+        ast::Expr::Rational { num, den } => Value::Rational { num: *num, den: *den },
+        ast::Expr::Float(f) => Value::Float(*f),
+        ast::Expr::Str(s) => Value::Str(s.clone()),
+        ast::Expr::Char(c) => Value::Char(*c),
+        ast::Expr::Symbol(sym) => Value::Symbol(sym.clone()),
+        ast::Expr::List(list) => Value::List(list.iter().map(quoted).collect()),
+        ast::Expr::Pair(car, cdr) => Value::Pair(quoted(car), quoted(cdr)),
+    })
+}
+
+/// The inverse of `quoted`: the expression denoting a value as data, if there
+/// is one (there isn't for functions).
+fn unquoted(value: &Value) -> Option<ast::Expr> {
+    Some(match value {
+        Value::Nil => ast::Expr::Nil,
+        Value::Bool(b) => ast::Expr::Bool(*b),
+        Value::Integer(i) => ast::Expr::Integer(*i),
+        Value::Rational { num, den } => ast::Expr::Rational { num: *num, den: *den },
+        Value::Float(f) => ast::Expr::Float(*f),
+        Value::Str(s) => ast::Expr::Str(s.clone()),
+        Value::Char(c) => ast::Expr::Char(*c),
+        Value::Symbol(sym) => ast::Expr::Symbol(Rc::clone(sym)),
+        Value::List(items) => ast::Expr::List(
+            items.iter().map(|item| unquoted(item)).collect::<Option<Vec<_>>>()?.into()
+        ),
+        Value::Pair(car, cdr) => ast::Expr::Pair(Rc::new(unquoted(car)?), Rc::new(unquoted(cdr)?)),
+        Value::Map(_) | Value::Bytes(_) | Value::Func(_) | Value::Promise(_) => return None,
+    })
+}
+
+thread_local! {
+    static GENSYM_COUNTER: Cell<u64> = const { Cell::new(0) };
+}
+
+fn gensym<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    let prefix = match args {
+        [] => "G__".to_string(),
+        [arg] => {
+            let value = env.eval(arg)?;
+            match value.as_ref() {
+                Value::Str(s) => Ok(s.clone()),
+                _ => Err(format!("Prefix '{}' for 'gensym' must be a string", value)),
+            }?
+        },
+        _ => return Err(EvalError::arity("gensym", "at most 1 argument")),
+    };
+    let n = GENSYM_COUNTER.with(|counter| {
+        let n = counter.get();
+        counter.set(n + 1);
+        n
+    });
+    Ok(Rc::new(Value::Symbol(ast::Symbol::from(format!("{}{}", prefix, n)))))
+}
+
+fn str_concat<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    let mut res = String::new();
+    for arg in args {
+        let value = env.eval(arg)?;
+        match value.as_ref() {
+            Value::Str(s) => res.push_str(s),
+            _ => return Err(EvalError::type_mismatch("str-concat", "string", &value)),
+        }
+    }
+    Ok(Rc::new(Value::Str(res)))
+}
+
+fn format<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    let (template, rest) = args.split_first().ok_or_else(|| EvalError::arity("format", "a template string"))?;
+    let template = env.eval(template)?;
+    let template = match template.as_ref() {
+        Value::Str(s) => Ok(s),
+        _ => Err(format!("Template '{}' in 'format' must be a string", template)),
+    }?;
+    let mut values = Vec::with_capacity(rest.len());
+    for arg in rest {
+        values.push(env.eval(arg)?);
+    }
+    let mut values = values.iter();
+    let mut res = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                res.push(c);
+                chars.next();
+            },
+            ('{', Some('}')) => {
+                let value = values.next().ok_or("Too few arguments for the placeholders in 'format'")?;
+                res.push_str(&value.to_string());
+                chars.next();
+            },
+            ('{', _) | ('}', _) => return Err(format!("Unmatched '{}' in 'format' template", c).into()),
+            _ => res.push(c),
+        }
+    }
+    if values.next().is_some() {
+        return Err("Too many arguments for the placeholders in 'format'".into());
+    }
+    Ok(Rc::new(Value::Str(res)))
+}
+
+fn str_length<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("str-length", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Str(s) => Ok(Rc::new(Value::Integer(s.chars().count() as i64))),
+        _ => Err(EvalError::type_mismatch("str-length", "string", &value)),
+    }
+}
+
+fn number_to_str<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("number->str", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Integer(_) | Value::Rational { .. } => Ok(Rc::new(Value::Str(value.to_string()))),
+        // debug formatting keeps the `.0` on whole floats, so they read back as floats
+        Value::Float(f) => Ok(Rc::new(Value::Str(format!("{:?}", f)))),
+        _ => Err(EvalError::type_mismatch("number->str", "number", &value)),
+    }
+}
+
+fn str_to_number<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("str->number", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    let s = match value.as_ref() {
+        Value::Str(s) => Ok(s.trim()),
+        _ => Err(EvalError::type_mismatch("str->number", "string", &value)),
+    }?;
+    if let Ok(i) = s.parse::<i64>() {
+        return Ok(Rc::new(Value::Integer(i)));
+    }
+    if s.contains('/') {
+        return match parser::parse_rational(s) {
+            Ok(expr) => Ok(quoted(&expr)),
+            Err(_) => Err(format!("Cannot parse '{}' as a number", s).into()),
+        };
+    }
+    match s.parse::<f64>() {
+        Ok(f) if f.is_finite() => Ok(Rc::new(Value::Float(f))),
+        _ => Err(format!("Cannot parse '{}' as a number", s).into()),
+    }
+}
+
+// indices count characters (Unicode scalar values), not bytes
+fn substring<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::arity("substring", "3 arguments only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    let start = env.eval(args.get(1).unwrap())?;
+    let end = env.eval(args.get(2).unwrap())?;
+    let s = match value.as_ref() {
+        Value::Str(s) => Ok(s),
+        _ => Err(EvalError::type_mismatch("substring", "string", &value)),
+    }?;
+    let (start, end) = match (start.as_ref(), end.as_ref()) {
+        (Value::Integer(start), Value::Integer(end)) => Ok((*start, *end)),
+        _ => Err(format!("Indices '{}' and '{}' in 'substring' must be integers", start, end)),
+    }?;
+    let len = s.chars().count() as i64;
+    if start < 0 || start > end || end > len {
+        return Err(format!("Range {}..{} out of bounds for string of length {}", start, end, len).into());
+    }
+    let res = s.chars().skip(start as usize).take((end - start) as usize).collect();
+    Ok(Rc::new(Value::Str(res)))
+}
+
+fn bytes<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    let mut res = Vec::with_capacity(args.len());
+    for arg in args {
+        let value = env.eval(arg)?;
+        let byte = match value.as_ref() {
+            Value::Integer(i) => u8::try_from(*i).ok(),
+            _ => None,
+        };
+        res.push(byte.ok_or(format!("Byte '{}' in 'bytes' must be an integer from 0 to 255", value))?);
+    }
+    Ok(Rc::new(Value::Bytes(res)))
+}
+
+fn byte_ref<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("byte-ref", "2 arguments only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    let index = env.eval(args.get(1).unwrap())?;
+    let bytes = match value.as_ref() {
+        Value::Bytes(bytes) => Ok(bytes),
+        _ => Err(EvalError::type_mismatch("byte-ref", "bytes", &value)),
+    }?;
+    let index = match index.as_ref() {
+        Value::Integer(i) => Ok(*i),
+        _ => Err(format!("Index '{}' in 'byte-ref' must be an integer", index)),
+    }?;
+    usize::try_from(index).ok()
+        .and_then(|i| bytes.get(i))
+        .map(|byte| Rc::new(Value::Integer((*byte).into())))
+        .ok_or(format!("Index {} out of range for bytes of length {}", index, bytes.len()).into())
+}
+
+fn bytes_length<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("bytes-length", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::Bytes(bytes) => Ok(Rc::new(Value::Integer(bytes.len() as i64))),
+        _ => Err(EvalError::type_mismatch("bytes-length", "bytes", &value)),
+    }
+}
+
+fn list<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    let mut items = Vec::with_capacity(args.len());
+    for arg in args {
+        items.push(env.eval(arg)?);
+    }
+    Ok(Rc::new(Value::List(items)))
+}
+
+fn car<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("car", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::List(list) => match list.first() {
+            Some(item) => Ok(Rc::clone(item)),
+            None => Err("Cannot apply 'car' to an empty list".into()),
+        },
+        Value::Pair(car, _) => Ok(Rc::clone(car)),
+        _ => Err(EvalError::type_mismatch("car", "list", &value)),
+    }
+}
+
+fn cdr<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("cdr", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::List(list) => match list.split_first() {
+            Some((_, rest)) => Ok(Rc::new(Value::List(rest.to_vec()))),
+            None => Err("Cannot apply 'cdr' to an empty list".into()),
+        },
+        Value::Pair(_, cdr) => Ok(Rc::clone(cdr)),
+        _ => Err(EvalError::type_mismatch("cdr", "list", &value)),
+    }
+}
+
+fn cons<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("cons", "2 arguments only"));
+    }
+    let head = env.eval(args.first().unwrap())?;
+    let tail = env.eval(args.get(1).unwrap())?;
+    match tail.as_ref() {
+        Value::List(list) => {
+            let mut items = Vec::with_capacity(list.len() + 1);
+            items.push(head);
+            items.extend(list.iter().cloned());
+            Ok(Rc::new(Value::List(items)))
+        },
+        _ => Ok(Rc::new(Value::Pair(head, tail))),
+    }
+}
+
+fn append<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    let mut res = Vec::new();
+    for arg in args {
+        let value = env.eval(arg)?;
+        match value.as_ref() {
+            Value::List(items) => res.extend(items.iter().cloned()),
+            _ => return Err(EvalError::type_mismatch("append", "list", &value)),
+        }
+    }
+    Ok(Rc::new(Value::List(res)))
+}
+
+fn nth<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("nth", "2 arguments only"));
+    }
+    let index = env.eval(args.first().unwrap())?;
+    let list = env.eval(args.get(1).unwrap())?;
+    let index = match index.as_ref() {
+        Value::Integer(i) => Ok(*i),
+        _ => Err(EvalError::type_mismatch("nth", "integer", &index)),
+    }?;
+    let items = match list.as_ref() {
+        Value::List(items) => Ok(items),
+        _ => Err(EvalError::type_mismatch("nth", "list", &list)),
+    }?;
+    usize::try_from(index).ok()
+        .and_then(|i| items.get(i))
+        .map(Rc::clone)
+        .ok_or(format!("Index {} out of range for list of length {}", index, items.len()).into())
+}
+
+fn len<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("len", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    match value.as_ref() {
+        Value::List(items) => Ok(Rc::new(Value::Integer(items.len() as i64))),
+        Value::Str(s) => Ok(Rc::new(Value::Integer(s.chars().count() as i64))),
+        _ => Err(EvalError::type_mismatch("len", "collection", &value)),
+    }
+}
+
+fn sort<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 && args.len() != 2 {
+        return Err(EvalError::arity("sort", "1 or 2 arguments"));
+    }
+    let list = env.eval(args.first().unwrap())?;
+    let items = match list.as_ref() {
+        Value::List(items) => Ok(items.clone()),
+        _ => Err(EvalError::type_mismatch("sort", "list", &list)),
+    }?;
+    let sorted = match args.get(1) {
+        Some(less) => {
+            let less = env.eval(less)?;
+            merge_sort(items, &mut |a, b| {
+                let res = env.apply(&less, vec![Rc::clone(a), Rc::clone(b)])?;
+                match res.as_ref() {
+                    Value::Bool(b) => Ok(*b),
+                    _ => Err(EvalError::type_mismatch("sort", "bool", &res)),
+                }
+            })
+        },
+        None => merge_sort(items, &mut |a, b| naturally_less(a, b)),
+    }?;
+    Ok(Rc::new(Value::List(sorted)))
+}
+
+/// The default order for `sort`, as with `<`, which only relates numbers of
+/// the same kind, strings with strings and chars with chars.
+fn naturally_less(a: &Value, b: &Value) -> Result<bool, EvalError> {
+    match (a, b) {
+        (Value::Integer(_) | Value::Rational { .. }, Value::Integer(_) | Value::Rational { .. }) =>
+            Ok(compare_fractions(fraction(a).unwrap(), fraction(b).unwrap()) == Ordering::Less),
+        (Value::Float(f), Value::Float(g)) => Ok(f < g),
+        (Value::Str(s), Value::Str(t)) => Ok(s < t),
+        (Value::Char(c), Value::Char(d)) => Ok(c < d),
+        _ => Err(EvalError::type_mismatch("sort", a.type_name(), b)),
+    }
+}
+
+// a stable sort which, unlike the standard library's, can fail part-way
+// through and tolerates an inconsistent order from a user-supplied comparator
+fn merge_sort<'a, F>(mut items: Vec<Rc<Value<'a>>>, less: &mut F) -> Result<Vec<Rc<Value<'a>>>, EvalError>
+where
+    F: FnMut(&Rc<Value<'a>>, &Rc<Value<'a>>) -> Result<bool, EvalError>,
+{
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, less)?;
+    let right = merge_sort(right, less)?;
+    let mut res = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // taking from the left unless the right is strictly less keeps it stable
+        if less(b, a)? {
+            res.push(right.next().unwrap());
+        } else {
+            res.push(left.next().unwrap());
+        }
+    }
+    res.extend(left);
+    res.extend(right);
+    Ok(res)
+}
+
+// any arguments between the function and the final list are passed before
+// the list's elements, as in `(apply + 1 2 (list 3 4))`
+fn apply<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::arity("apply", "at least 2 arguments"));
+    }
+    let (func, rest) = args.split_first().unwrap();
+    let (list, leading) = rest.split_last().unwrap();
+    let func = env.eval(func)?;
+    if !matches!(func.as_ref(), Value::Func(_)) {
+        return Err(EvalError::type_mismatch("apply", "function", &func));
+    }
+    let mut values = Vec::with_capacity(leading.len());
+    for arg in leading {
+        values.push(env.eval(arg)?);
+    }
+    let list = env.eval(list)?;
+    match list.as_ref() {
+        Value::List(items) => values.extend(items.iter().cloned()),
+        _ => return Err(EvalError::type_mismatch("apply", "list", &list)),
+    }
+    env.apply(&func, values)
+}
+
+fn delay<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Tail<'a>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("delay", "1 argument only"));
+    }
+    Ok(Tail::Done(Rc::new(Value::Promise(Rc::new(Promise {
+        expr: args.first().unwrap().clone(),
+        env: env.clone(),
+        value: RefCell::new(None),
+        forcing: Cell::new(false),
+    })))))
+}
+
+// forcing anything other than a promise just gives it back
+fn force<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("force", "1 argument only"));
+    }
+    let value = env.eval(args.first().unwrap())?;
+    let promise = match value.as_ref() {
+        Value::Promise(promise) => promise,
+        _ => return Ok(value),
+    };
+    if let Some(value) = promise.value.borrow().as_ref() {
+        return Ok(Rc::clone(value));
+    }
+    if promise.forcing.get() {
+        return Err("Cannot force a promise while it's already being forced".into());
+    }
+    let _call = env.enter_call()?;
+    promise.forcing.set(true);
+    let res = promise.env.clone().eval(&promise.expr);
+    promise.forcing.set(false);
+    let value = res?;
+    *promise.value.borrow_mut() = Some(Rc::clone(&value));
+    Ok(value)
+}
+
+fn memoize<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity("memoize", "1 argument only"));
+    }
+    let func = env.eval(args.first().unwrap())?;
+    if !matches!(func.as_ref(), Value::Func(_)) {
+        return Err(format!("Cannot 'memoize' non-function '{}'", func).into());
+    }
+    Ok(Rc::new(Value::Func(Func::Memoized {
+        func,
+        cache: Rc::new(RefCell::new(HashMap::new())),
+    })))
+}
+
+fn hash_map<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(EvalError::arity("hash-map", "an even number of arguments"));
+    }
+    let mut map = HashMap::with_capacity(args.len() / 2);
+    for pair in args.chunks(2) {
+        let key = env.eval(&pair[0])?;
+        let value = env.eval(&pair[1])?;
+        map.insert(map_key(&key)?, value);
+    }
+    Ok(Rc::new(Value::Map(map)))
+}
+
+fn hash_get<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 && args.len() != 3 {
+        return Err(EvalError::arity("hash-get", "2 or 3 arguments"));
+    }
+    let map = env.eval(args.first().unwrap())?;
+    let key = env.eval(args.get(1).unwrap())?;
+    let map = match map.as_ref() {
+        Value::Map(map) => Ok(map),
+        _ => Err(EvalError::type_mismatch("hash-get", "map", &map)),
+    }?;
+    match map.get(&map_key(&key)?) {
+        Some(value) => Ok(Rc::clone(value)),
+        None => match args.get(2) {
+            Some(default) => env.eval(default),
+            None => Ok(Rc::new(Value::Nil)),
+        },
+    }
+}
+
+// maps are immutable: this gives an updated copy
+fn hash_set<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::arity("hash-set", "3 arguments only"));
+    }
+    let map = env.eval(args.first().unwrap())?;
+    let key = env.eval(args.get(1).unwrap())?;
+    let value = env.eval(args.get(2).unwrap())?;
+    let mut map = match map.as_ref() {
+        Value::Map(map) => Ok(map.clone()),
+        _ => Err(EvalError::type_mismatch("hash-set", "map", &map)),
+    }?;
+    map.insert(map_key(&key)?, value);
+    Ok(Rc::new(Value::Map(map)))
+}
+
+fn map_key(key: &Value) -> Result<ast::Expr, EvalError> {
+    unquoted(key).ok_or_else(|| format!("Cannot use '{}' as a map key", key).into())
+}
+
+fn map<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("map", "2 arguments only"));
+    }
+    let func = env.eval(args.first().unwrap())?;
+    let list = env.eval(args.get(1).unwrap())?;
+    let items = match list.as_ref() {
+        Value::List(items) => Ok(items),
+        _ => Err(EvalError::type_mismatch("map", "list", &list)),
+    }?;
+    let mut res = Vec::with_capacity(items.len());
+    for item in items {
+        res.push(env.apply(&func, vec![Rc::clone(item)])?);
+    }
+    Ok(Rc::new(Value::List(res)))
+}
+
+fn filter<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity("filter", "2 arguments only"));
+    }
+    let func = env.eval(args.first().unwrap())?;
+    let list = env.eval(args.get(1).unwrap())?;
+    let items = match list.as_ref() {
+        Value::List(items) => Ok(items),
+        _ => Err(EvalError::type_mismatch("filter", "list", &list)),
+    }?;
+    let mut res = Vec::new();
+    for item in items {
+        let keep = env.apply(&func, vec![Rc::clone(item)])?;
+        match keep.as_ref() {
+            Value::Bool(true) => res.push(Rc::clone(item)),
+            Value::Bool(false) => {},
+            _ => return Err(EvalError::type_mismatch("filter", "bool", &keep)),
+        }
+    }
+    Ok(Rc::new(Value::List(res)))
+}
+
+fn reduce<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::arity("reduce", "3 arguments only"));
+    }
+    let func = env.eval(args.first().unwrap())?;
+    let mut acc = env.eval(args.get(1).unwrap())?;
+    let list = env.eval(args.get(2).unwrap())?;
+    let items = match list.as_ref() {
+        Value::List(items) => Ok(items),
+        _ => Err(EvalError::type_mismatch("reduce", "list", &list)),
+    }?;
+    for item in items {
+        acc = env.apply(&func, vec![acc, Rc::clone(item)])?;
+    }
+    Ok(acc)
+}
+
+impl Value<'_> {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "bool",
+            Value::Integer(_) => "integer",
+            Value::Rational { .. } => "rational",
+            Value::Float(_) => "float",
+            Value::Str(_) => "string",
+            Value::Char(_) => "char",
+            Value::Symbol(_) => "symbol",
+            Value::List(_) => "list",
+            Value::Pair(..) => "pair",
+            Value::Map(_) => "map",
+            Value::Bytes(_) => "bytes",
+            Value::Promise(_) => "promise",
+            Value::Func(_) => "function",
+        }
+    }
+
+    /// Like `Display`, but a list too wide to fit on one line (when starting
+    /// `indent` columns in) has its elements put on separate lines, aligned
+    /// under the first. Floats are rounded to `precision` significant digits
+    /// if given.
+    pub fn pretty(&self, indent: usize, precision: Option<usize>) -> String {
+        let flat = with_precision(self, precision);
+        match self {
+            Value::List(items) if !items.is_empty() && indent + flat.chars().count() > PRETTY_WIDTH => {
+                let mut res = String::from("(");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        res.push('\n');
+                        res.push_str(&" ".repeat(indent + 1));
+                    }
+                    res.push_str(&item.pretty(indent + 1, precision));
+                }
+                res.push(')');
+                res
+            },
+            _ => flat,
+        }
+    }
+}
+
+const PRETTY_WIDTH: usize = 60;
+
+/// Structural equality; values of differing types are unequal, and functions
+/// are never equal to anything (not even themselves).
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(x), Value::Bool(y)) => x == y,
+            (Value::Integer(x), Value::Integer(y)) => x == y,
+            (Value::Rational { num: a, den: b }, Value::Rational { num: c, den: d }) => a == c && b == d,
+            (Value::Float(x), Value::Float(y)) => x == y,
+            (Value::Str(x), Value::Str(y)) => x == y,
+            (Value::Char(x), Value::Char(y)) => x == y,
+            (Value::Symbol(x), Value::Symbol(y)) => x == y,
+            (Value::List(x), Value::List(y)) => x == y,
+            (Value::Pair(a, b), Value::Pair(c, d)) => a == c && b == d,
+            (Value::Map(x), Value::Map(y)) => x == y,
+            (Value::Bytes(x), Value::Bytes(y)) => x == y,
+            (Value::Promise(x), Value::Promise(y)) => Rc::ptr_eq(x, y),
+            _ => false,
+        }
+    }
+}
+
+fn with_precision(value: &Value, precision: Option<usize>) -> String {
+    match precision {
+        Some(digits) => format!("{:.*}", digits, value),
+        None => value.to_string(),
+    }
+}
+
+/// A precision, as in `{:.3}`, rounds floats (including those inside lists and
+/// maps) to that many significant digits.
+impl Display for Value<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        let precision = fmt.precision();
+        match self {
+            Value::Nil => write!(fmt, "nil"),
+            Value::Bool(b) => write!(fmt, "{}", b),
+            Value::Integer(i) => write!(fmt, "{}", i),
+            Value::Rational { num, den } => write!(fmt, "{}/{}", num, den),
+            // shown as briefly as possible, so whole floats have no `.0` (`1.0`
+            // prints as 1); rounding goes via scientific notation, which keeps
+            // exactly the significant digits asked for
+            Value::Float(f) => match precision {
+                Some(digits) => {
+                    let digits = digits.max(1);
+                    let rounded: f64 = format!("{:.*e}", digits - 1, f).parse().unwrap_or(*f);
+                    write!(fmt, "{}", rounded)
+                },
+                None => write!(fmt, "{}", f),
+            },
+            Value::Str(s) => write!(fmt, "{}", s),
+            Value::Char(c) => write!(fmt, "{}", c),
+            Value::Symbol(sym) => write!(fmt, "{}", sym),
+            Value::List(list) => {
+                write!(fmt, "(")?;
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 { write!(fmt, " ")?; }
+                    write!(fmt, "{}", with_precision(item, precision))?;
+                }
+                write!(fmt, ")")
+            },
+            Value::Pair(car, cdr) => {
+                write!(fmt, "({}", with_precision(car, precision))?;
+                let mut cdr = cdr;
+                while let Value::Pair(next, rest) = cdr.as_ref() {
+                    write!(fmt, " {}", with_precision(next, precision))?;
+                    cdr = rest;
+                }
+                write!(fmt, " . {})", with_precision(cdr, precision))
+            },
+            // entries are sorted, so equal maps always look the same; whole float
+            // keys keep their `.0`, since `1.0` and `1` are different keys
+            Value::Map(map) => {
+                let mut entries: Vec<_> = map.iter()
+                    .map(|(key, value)| format!("{} {}", shown_key(key, precision), with_precision(value, precision)))
+                    .collect();
+                entries.sort();
+                write!(fmt, "{{{}}}", entries.join(", "))
+            },
+            Value::Bytes(bytes) => {
+                let bytes: Vec<_> = bytes.iter().map(|byte| byte.to_string()).collect();
+                write!(fmt, "#bytes[{}]", bytes.join(" "))
+            },
+            // not showing the value, which may contain the promise itself
+            Value::Promise(promise) if promise.value.borrow().is_some() => write!(fmt, "<promise: forced>"),
+            Value::Promise(_) => write!(fmt, "<promise>"),
+            Value::Func(func) => write!(fmt, "{}", func),
+        }
+    }
+}
+
+fn shown_key(key: &ast::Expr, precision: Option<usize>) -> String {
+    let shown = with_precision(&quoted(key), precision);
+    match key {
+        ast::Expr::Float(f) if f.is_finite() && !shown.contains('.') => format!("{}.0", shown),
+        _ => shown,
+    }
+}
+
+impl Display for Func<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Func::BuiltIn { name, .. } | Func::SpecialForm { name, .. } =>
+                write!(fmt, "<built-in function '{}'>", name),
+            Func::UserDefined { params, .. } => {
+                let params: Vec<_> = params.iter().map(|param| param.to_string()).collect();
+                write!(fmt, "<fn ({})>", params.join(" "))
+            },
+            Func::Memoized { func, .. } => write!(fmt, "<memoized {}>", func),
+        }
+    }
+}
+
+
+// closures aren't printed with their captured scope, which may contain the
+// closure itself
+impl Debug for Func<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Func::BuiltIn { name, .. } => write!(fmt, "BuiltIn({:?})", name),
+            Func::SpecialForm { name, .. } => write!(fmt, "SpecialForm({:?})", name),
+            Func::UserDefined { params, .. } => write!(fmt, "UserDefined({} params)", params.len()),
+            Func::Memoized { func, .. } => write!(fmt, "Memoized({:?})", func),
+        }
+    }
+}
+
+/// Floating-point maths built-ins. Integer arguments are promoted to floats,
+/// and the result is always a float.
+mod math {
+    use std::rc::Rc;
+
+    use super::{Env, EvalError, Value};
+    use crate::ast;
+
+    fn float_arg(env: &mut Env, arg: &ast::Expr, name: &str) -> Result<f64, EvalError> {
+        let value = env.eval(arg)?;
+        match value.as_ref() {
+            Value::Integer(i) => Ok(*i as f64),
+            Value::Rational { num, den } => Ok(*num as f64 / *den as f64),
+            Value::Float(f) => Ok(*f),
+            _ => Err(EvalError::type_mismatch(name, "number", &value)),
+        }
+    }
+
+    // a NaN from non-NaN arguments means they were outside the function's domain
+    fn defined<'a>(res: f64, args: &[f64], name: &str) -> Result<Rc<Value<'a>>, EvalError> {
+        if res.is_nan() && !args.iter().any(|arg| arg.is_nan()) {
+            let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+            return Err(format!("'{}' is undefined for {}", name, args.join(" and ")).into());
+        }
+        Ok(Rc::new(Value::Float(res)))
+    }
+
+    fn unary<'a>(
+        env: &mut Env<'a>,
+        args: &[ast::Expr],
+        name: &str,
+        op: fn(f64) -> f64,
+    ) -> Result<Rc<Value<'a>>, EvalError> {
+        if args.len() != 1 {
+            return Err(EvalError::arity(name, "1 argument only"));
+        }
+        let x = float_arg(env, args.first().unwrap(), name)?;
+        defined(op(x), &[x], name)
+    }
+
+    pub fn sqrt<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+        unary(env, args, "sqrt", f64::sqrt)
+    }
+
+    pub fn sin<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+        unary(env, args, "sin", f64::sin)
+    }
+
+    pub fn cos<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+        unary(env, args, "cos", f64::cos)
+    }
+
+    pub fn tan<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+        unary(env, args, "tan", f64::tan)
+    }
+
+    pub fn pow<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+        if args.len() != 2 {
+            return Err(EvalError::arity("pow", "2 arguments only"));
+        }
+        let base = float_arg(env, args.first().unwrap(), "pow")?;
+        let exp = float_arg(env, args.get(1).unwrap(), "pow")?;
+        defined(base.powf(exp), &[base, exp], "pow")
+    }
+
+    /// The natural logarithm, or the logarithm to the given base.
+    pub fn log<'a>(env: &mut Env<'a>, args: &[ast::Expr]) -> Result<Rc<Value<'a>>, EvalError> {
+        match args {
+            [x] => {
+                let x = float_arg(env, x, "log")?;
+                defined(x.ln(), &[x], "log")
+            },
+            [x, base] => {
+                let x = float_arg(env, x, "log")?;
+                let base = float_arg(env, base, "log")?;
+                defined(x.log(base), &[x, base], "log")
+            },
+            _ => Err(EvalError::arity("log", "1 or 2 arguments")),
+        }
+    }
+}
+
+impl Debug for Promise<'_> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        let state = if self.value.borrow().is_some() { "forced" } else { "pending" };
+        write!(fmt, "Promise({:?}, {})", self.expr, state)
+    }
+}
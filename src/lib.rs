@@ -0,0 +1,33 @@
+use std::rc::Rc;
+
+pub mod ast;
+pub mod eval;
+pub mod parser;
+
+use eval::{Env, EvalError, Value};
+
+
+/// Parses `src` as a script and evaluates each expression in turn in `env`,
+/// returning the value of the last one (nil for an empty script).
+///
+/// Evaluation recurses on the calling thread's stack, and `env`'s recursion
+/// limit must fit within it (see `Env::with_recursion_limit`).
+pub fn eval_str<'a>(src: &str, env: &mut Env<'a>) -> Result<Rc<Value<'a>>, EvalError> {
+    let script = parser::ScriptParser::new()
+        .parse(src)
+        .map_err(|err| {
+            let (line, column, message) = parser::locate_error(src, &err);
+            EvalError::Parse { line, column, message }
+        })?;
+    eval_script(&script, env)
+}
+
+/// Evaluates each expression of an already-parsed script in turn in `env`,
+/// returning the value of the last one (nil for an empty script).
+pub fn eval_script<'a>(script: &ast::Script, env: &mut Env<'a>) -> Result<Rc<Value<'a>>, EvalError> {
+    let mut last = Rc::new(Value::Nil);
+    for expr in script.0.iter() {
+        last = env.eval(expr)?;
+    }
+    Ok(last)
+}
@@ -1,20 +1,38 @@
+use std::env;
+use std::fs;
 use std::io;
-use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
 
 use clap::{Parser, Subcommand};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
 
-pub mod ast;
-pub mod eval;
-pub mod parser;
+use my_first_lisp::{eval, eval_script, parser};
 
 
 #[derive(Parser)]
+#[command(version)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
 
     #[arg(short, long)]
     debug: bool,
+
+    /// Print plain-text prefixes instead of emoji
+    #[arg(long)]
+    no_emoji: bool,
+
+    /// Show floats rounded to N significant digits
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    float_precision: Option<u32>,
 }
 
 #[derive(Subcommand)]
@@ -23,40 +41,345 @@ enum Commands {
         path: String,
     },
     Repl,
+    /// Evaluate an expression and print its value
+    Eval {
+        // so negative numbers such as `-5` aren't taken for options
+        #[arg(allow_hyphen_values = true)]
+        expr: String,
+    },
 }
 
 
+// non-tail recursion in a program recurses natively in the evaluator, so the
+// interpreter gets a far bigger stack than the main thread's, which allows a
+// far deeper recursion limit than the library's default
+const STACK_SIZE: usize = 1 << 30;
+const RECURSION_LIMIT: usize = 10_000;
+
+/// How results and errors are labelled when printed.
+struct Style {
+    result: &'static str,
+    error: &'static str,
+    parser_error: &'static str,
+    bye: &'static str,
+}
+
+const EMOJI: Style = Style {
+    result: "🔥 ",
+    error: "😱 ERROR",
+    parser_error: "😱 PARSER ERROR",
+    bye: "👋 bye!",
+};
+
+const PLAIN: Style = Style {
+    result: "=> ",
+    error: "ERROR",
+    parser_error: "PARSER ERROR",
+    bye: "bye!",
+};
+
 fn main() {
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("failed to spawn interpreter thread");
+    if interpreter.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run() {
 
     let cli = Cli::parse();
+    let style = if cli.no_emoji { &PLAIN } else { &EMOJI };
+    let float_precision = cli.float_precision.map(|digits| digits as usize);
 
     match cli.command {
-        Commands::Run { path: _ } => {
-            unimplemented!();
+        Commands::Run { path } => {
+            // `-` reads the script from stdin, e.g. `cat prog.lisp | my-first-lisp run -`
+            let script = if path == "-" {
+                io::read_to_string(io::stdin())
+            } else {
+                fs::read_to_string(&path)
+            };
+            let script = match script {
+                Ok(script) => script,
+                Err(msg) => {
+                    eprintln!("{}: cannot read '{}': {}", style.error, path, msg);
+                    process::exit(1);
+                }
+            };
+            let script = parser::strip_shebang(&script);
+            let inst = parser::ScriptParser::new();
+            let tree = match inst.parse(script) {
+                Ok(tree) => tree,
+                Err(msg) => {
+                    eprintln!("\n{}: {}", style.parser_error, parser::format_error(script, &msg));
+                    process::exit(1);
+                }
+            };
+            let mut env = eval::Env::default()
+                .with_recursion_limit(RECURSION_LIMIT)
+                .with_float_precision(float_precision);
+            let mut last = None;
+            for expr in tree.0.iter() {
+                match env.eval(expr) {
+                    Ok(res) => last = Some(res),
+                    Err(msg) => {
+                        eprintln!("{}: {}", style.error, msg);
+                        if cli.debug { eprintln!("   TREE:  {}", expr); }
+                        process::exit(1);
+                    }
+                }
+            }
+            if cli.debug {
+                if let Some(res) = last { println!("{}{}", style.result, env.display(&res)); }
+            }
+        },
+        Commands::Eval { expr } => {
+            let tree = match parser::ScriptParser::new().parse(&expr) {
+                Ok(tree) => tree,
+                Err(msg) => {
+                    eprintln!("{}: {}", style.parser_error, parser::format_error(&expr, &msg));
+                    process::exit(1);
+                }
+            };
+            let mut env = eval::Env::default()
+                .with_recursion_limit(RECURSION_LIMIT)
+                .with_float_precision(float_precision);
+            match eval_script(&tree, &mut env) {
+                // printed bare, so it's easy to use from shell scripts
+                Ok(res) => println!("{}", env.display(&res)),
+                Err(msg) => {
+                    eprintln!("{}: {}", style.error, msg);
+                    process::exit(1);
+                }
+            }
         },
         Commands::Repl => {
             let inst = parser::ExprParser::new();
-            let mut env = eval::Env::default();
+            let mut env = eval::Env::default()
+                .with_recursion_limit(RECURSION_LIMIT)
+                .with_float_precision(float_precision);
+            let mut editor = match Editor::<SymbolCompleter, DefaultHistory>::new() {
+                Ok(editor) => editor,
+                Err(msg) => {
+                    eprintln!("{}: cannot start line editor: {}", style.error, msg);
+                    process::exit(1);
+                }
+            };
+            let history = history_path();
+            if let Some(path) = &history {
+                // there's no history file on the very first run
+                let _ = editor.load_history(path);
+            }
+            // lines are gathered until their parentheses balance, so a single
+            // expression may span several of them
+            let mut script = String::new();
+            // the bindings before each expression that changed them, for `:undo`
+            let mut undo = Vec::new();
+            editor.set_helper(Some(SymbolCompleter::default()));
             loop {
-                print!("sam's lisp >> ");
-                io::stdout().flush().unwrap();
-
-                let mut script = String::new();
-                let _ = io::stdin().read_line(&mut script);
+                if let Some(completer) = editor.helper_mut() {
+                    completer.symbols = env.bindings().into_iter().map(|(sym, _)| sym.to_string()).collect();
+                }
+                let prompt = if script.is_empty() { "sam's lisp >> " } else { "           ... " };
+                let line = match editor.readline(prompt) {
+                    Ok(line) => line,
+                    Err(ReadlineError::Interrupted) => {
+                        script.clear();
+                        continue;
+                    },
+                    Err(ReadlineError::Eof) => {
+                        println!("{}", style.bye);
+                        break;
+                    },
+                    Err(msg) => {
+                        println!("{}: {}", style.error, msg);
+                        break;
+                    }
+                };
+                if script.is_empty() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let _ = editor.add_history_entry(line.as_str());
+                    if line.trim_start().starts_with(';') {
+                        continue;
+                    }
+                    if line.trim() == ":env" {
+                        print_bindings(&env);
+                        continue;
+                    }
+                    // reloading a file being worked on is undone in one step
+                    let command = line.trim();
+                    if command == ":load" || command.starts_with(":load ") {
+                        let path = command[":load".len()..].trim();
+                        if path.is_empty() {
+                            println!("{}: usage: :load <path>", style.error);
+                            continue;
+                        }
+                        let snapshot = env.snapshot();
+                        match env.load_file(path) {
+                            Ok(_) => println!("loaded {}", path),
+                            Err(msg) => println!("{}: {}", style.error, msg),
+                        }
+                        if env.changed_since(&snapshot) {
+                            undo.push(snapshot);
+                        }
+                        continue;
+                    }
+                    // the bindings are put back afterwards, so e.g. `:type (def x 1)`
+                    // doesn't define x, though other side effects such as printing
+                    // or writing files still happen
+                    if command == ":type" || command.starts_with(":type ") {
+                        let expr = command[":type".len()..].trim();
+                        if expr.is_empty() {
+                            println!("{}: usage: :type <expr>", style.error);
+                            continue;
+                        }
+                        let snapshot = env.snapshot();
+                        match inst.parse(expr) {
+                            Ok(tree) => match env.eval(&tree) {
+                                Ok(res) => println!("{}{}", style.result, res.type_name()),
+                                Err(msg) => println!("{}: {}", style.error, msg),
+                            },
+                            Err(msg) => println!("\n{}: {}", style.parser_error, parser::format_error(expr, &msg)),
+                        }
+                        env.restore(snapshot);
+                        continue;
+                    }
+                    if line.trim() == ":undo" {
+                        match undo.pop() {
+                            Some(snapshot) => env.restore(snapshot),
+                            None => println!("{}: nothing to undo", style.error),
+                        }
+                        continue;
+                    }
+                } else {
+                    let _ = editor.add_history_entry(line.as_str());
+                    script.push('\n');
+                }
+                script.push_str(&line);
+                if parser::unclosed_parens(&script) > 0 {
+                    continue;
+                }
                 match inst.parse(&script) {
                     Ok(tree) => {
+                        let snapshot = env.snapshot();
                         let tree_cloned = tree.clone();
                         match env.eval(&tree_cloned) {
-                            Ok(res) => println!("🔥 {}", res),
+                            Ok(res) => println!("{}{}", style.result, env.display(&res)),
                             Err(msg) => {
-                                println!("😱 ERROR: {}", msg);
+                                println!("{}: {}", style.error, msg);
                                 if cli.debug { println!("   TREE:  {}", tree); }
                             }
                         }
+                        if env.changed_since(&snapshot) {
+                            undo.push(snapshot);
+                        }
                     },
-                    Err(msg) => println!("\n😱 PARSER ERROR: {}", msg),
+                    Err(msg) => println!("\n{}: {}", style.parser_error, parser::format_error(&script, &msg)),
+                }
+                script.clear();
+            }
+            if let Some(path) = &history {
+                if let Err(msg) = editor.save_history(path) {
+                    eprintln!("{}: cannot save history to '{}': {}", style.error, path.display(), msg);
                 }
             }
         },
     }
 }
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".sams_lisp_history"))
+}
+
+fn print_bindings(env: &eval::Env) {
+    for (sym, value) in env.bindings() {
+        match value.as_ref() {
+            eval::Value::Func(eval::Func::BuiltIn { name, .. })
+            | eval::Value::Func(eval::Func::SpecialForm { name, .. }) =>
+                println!("  {} : built-in function '{}'", sym, name),
+            eval::Value::Func(eval::Func::UserDefined { .. }) =>
+                println!("  {} : user-defined function", sym),
+            eval::Value::Func(eval::Func::Memoized { .. }) =>
+                println!("  {} : memoized function", sym),
+            _ => println!("  {} : {} = {}", sym, value.type_name(), env.display(&value)),
+        }
+    }
+}
+
+/// Completes the symbol under the cursor in the REPL from the names bound in
+/// the environment.
+#[derive(Default)]
+struct SymbolCompleter {
+    symbols: Vec<String>,
+}
+
+impl SymbolCompleter {
+    /// Where the word ending at `pos` starts, and the symbols it's a prefix of.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || "()'\";".contains(c))
+            .map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        let matches = self.symbols.iter()
+            .filter(|sym| sym.starts_with(prefix))
+            .cloned()
+            .collect();
+        (start, matches)
+    }
+}
+
+impl Completer for SymbolCompleter {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for SymbolCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for SymbolCompleter {}
+
+impl Validator for SymbolCompleter {}
+
+impl Helper for SymbolCompleter {}
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolCompleter;
+
+    fn completer() -> SymbolCompleter {
+        let symbols = ["str-concat", "str-length", "substring", "+"];
+        SymbolCompleter { symbols: symbols.iter().map(|sym| sym.to_string()).collect() }
+    }
+
+    #[test]
+    fn completes_the_symbol_before_the_cursor() {
+        let line = "(print (str-";
+        assert_eq!(completer().candidates(line, line.len()), (8, vec!["str-concat".to_string(), "str-length".to_string()]));
+        assert_eq!(completer().candidates("sub", 3), (0, vec!["substring".to_string()]));
+    }
+
+    #[test]
+    fn words_end_at_the_cursor() {
+        assert_eq!(completer().candidates("(str-concat x)", 5), (1, vec!["str-concat".to_string(), "str-length".to_string()]));
+    }
+
+    #[test]
+    fn quotes_and_strings_start_a_new_word() {
+        assert_eq!(completer().candidates("'su", 3), (1, vec!["substring".to_string()]));
+        assert_eq!(completer().candidates("\"+", 2), (1, vec!["+".to_string()]));
+    }
+
+    #[test]
+    fn unknown_prefixes_have_no_candidates() {
+        assert_eq!(completer().candidates("(zz", 3), (1, vec![]));
+    }
+}
@@ -1,6 +1,136 @@
-use lalrpop_util::lalrpop_mod;
+use lalrpop_util::lexer::Token;
+use lalrpop_util::{lalrpop_mod, ParseError};
 
-lalrpop_mod!(pub grammar);
+use crate::ast;
+
+lalrpop_mod!(#[allow(clippy::all)] pub grammar);
 
 pub type ExprParser = grammar::ExprParser;
 pub type ScriptParser = grammar::ScriptParser;
+
+/// Describes a parse error in terms of the 1-based line and column of `source`
+/// it occurred at, rather than a byte offset.
+pub fn format_error(source: &str, error: &ParseError<usize, Token, (usize, &str)>) -> String {
+    let (line, column, message) = locate_error(source, error);
+    format!("parse error at line {}, column {}: {}", line, column, message)
+}
+
+/// The 1-based line and column of `source` a parse error occurred at, and what
+/// went wrong there.
+pub fn locate_error(source: &str, error: &ParseError<usize, Token, (usize, &str)>) -> (usize, usize, String) {
+    let (location, message) = match error {
+        ParseError::InvalidToken { location } => (*location, "invalid token".to_string()),
+        ParseError::UnrecognizedEof { location, .. } => (*location, "unexpected end of input".to_string()),
+        ParseError::UnrecognizedToken { token: (start, token, _), .. } =>
+            (*start, format!("unexpected token '{}'", token)),
+        ParseError::ExtraToken { token: (start, token, _) } => (*start, format!("extra token '{}'", token)),
+        ParseError::User { error: (location, message) } => (*location, message.to_string()),
+    };
+    let (line, column) = line_and_column(source, location);
+    (line, column, message)
+}
+
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = match before.rfind('\n') {
+        Some(i) => before[i + 1..].chars().count() + 1,
+        None => before.chars().count() + 1,
+    };
+    (line, column)
+}
+
+/// Parses an integer literal in the given radix, allowing a leading `-`, a
+/// `0x`/`0b` prefix for radix 16/2 and `_` digit separators.
+pub fn parse_integer(s: &str, radix: u32) -> Result<i64, &'static str> {
+    let (sign, digits) = match s.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", s),
+    };
+    let digits = match radix {
+        10 => digits,
+        _ => &digits[2..],
+    };
+    i64::from_str_radix(&format!("{}{}", sign, digits.replace('_', "")), radix)
+        .map_err(|_| "Integer literal out of range")
+}
+
+/// Parses a rational literal such as `3/4` (or `-6/8`, which is `-3/4`), giving
+/// an integer if it's a whole number.
+pub fn parse_rational(s: &str) -> Result<ast::Expr, &'static str> {
+    let (num, den) = s.split_once('/').ok_or("Invalid rational literal")?;
+    let num = parse_integer(num, 10)?;
+    let den = parse_integer(den, 10)?;
+    if den == 0 {
+        return Err("Zero denominator in rational literal");
+    }
+    match ast::reduce_fraction(num.into(), den.into()) {
+        Some((num, 1)) => Ok(ast::Expr::Integer(num)),
+        Some((num, den)) => Ok(ast::Expr::Rational { num, den }),
+        None => Err("Rational literal out of range"),
+    }
+}
+
+/// Skips a `#!` line at the very start of a script file, so it can be made
+/// executable. The line's newline is kept, so line numbers in parse errors
+/// still match the file.
+pub fn strip_shebang(source: &str) -> &str {
+    match source.strip_prefix("#!") {
+        Some(rest) => rest.find('\n').map_or("", |i| &rest[i..]),
+        None => source,
+    }
+}
+
+/// Resolves the escape sequences in the body of a string literal.
+pub fn unescape(s: &str) -> Result<String, &'static str> {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('"') => res.push('"'),
+            Some('\\') => res.push('\\'),
+            _ => return Err("Invalid escape sequence in string literal"),
+        }
+    }
+    Ok(res)
+}
+
+/// Counts the parentheses opened but not yet closed in `source`, ignoring any
+/// in strings, character literals and comments. Negative if there are more `)`
+/// than `(`.
+pub fn unclosed_parens(source: &str) -> isize {
+    let mut depth = 0;
+    let mut chars = source.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            // a character literal such as `\(`
+            '\\' => { chars.next(); },
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            },
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => { chars.next(); },
+                        '"' => break,
+                        _ => {},
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+    depth
+}
@@ -0,0 +1,92 @@
+// counts heap allocations made by evaluation, to check that the hot paths of
+// a call don't copy more than they need to
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use my_first_lisp::eval::Env;
+use my_first_lisp::{eval_script, eval_str, parser};
+
+struct Counting;
+
+thread_local! {
+    // tests run on several threads at once, so each counts its own
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Evaluates `src` in `env`, giving its value and how many allocations that
+/// took (parsing aside).
+fn count_allocations(src: &str, env: &mut Env) -> (String, usize) {
+    let script = parser::ScriptParser::new().parse(src).unwrap();
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = eval_script(&script, env).unwrap();
+    let count = ALLOCATIONS.with(Cell::get) - before;
+    (value.to_string(), count)
+}
+
+const FACTORIAL: &str = "(defn fact (n) (if (= n 0) 1 (* n (fact (- n 1)))))";
+
+#[test]
+fn factorial_of_20() {
+    let mut env = Env::default();
+    eval_str(FACTORIAL, &mut env).unwrap();
+    let (value, _) = count_allocations("(fact 20)", &mut env);
+    assert_eq!(value, "2432902008176640000");
+}
+
+#[test]
+fn calls_do_not_copy_the_environment() {
+    let mut small = Env::default();
+    eval_str(FACTORIAL, &mut small).unwrap();
+    let mut large = Env::default();
+    eval_str(FACTORIAL, &mut large).unwrap();
+    let defs: Vec<_> = (0..1000).map(|i| format!("(def x{} {})", i, i)).collect();
+    eval_str(&defs.join(" "), &mut large).unwrap();
+    let (_, in_small) = count_allocations("(fact 20)", &mut small);
+    let (_, in_large) = count_allocations("(fact 20)", &mut large);
+    assert_eq!(in_small, in_large);
+    // a handful per call for its frame and argument values, however big the
+    // environment it was called from
+    assert!(in_small < 21 * 20, "{} allocations", in_small);
+}
+
+#[test]
+fn symbol_lookups_do_not_allocate() {
+    let mut env = Env::default();
+    eval_str("(def x 1)", &mut env).unwrap();
+    let few = format!("(+ {})", ["x"; 2].join(" "));
+    let many = format!("(+ {})", ["x"; 200].join(" "));
+    let (_, for_few) = count_allocations(&few, &mut env);
+    let (value, for_many) = count_allocations(&many, &mut env);
+    assert_eq!(value, "200");
+    assert_eq!(for_few, for_many);
+}
+
+#[test]
+fn lookup_heavy_loops_allocate_per_iteration_not_per_lookup() {
+    let mut env = Env::default();
+    eval_str("(def a 1) (def b 2) (def c 3)", &mut env).unwrap();
+    let mut run = |lookups: usize| {
+        let sum = vec!["a b c"; lookups].join(" ");
+        let src = format!("(def total 0) (dotimes (i 100) (set! total (+ total {}))) total", sum);
+        count_allocations(&src, &mut env)
+    };
+    let (_, for_few) = run(1);
+    let (value, for_many) = run(50);
+    assert_eq!(value, "30000");
+    assert_eq!(for_few, for_many);
+}
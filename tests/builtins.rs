@@ -0,0 +1,366 @@
+mod common;
+
+use std::{fs, process};
+
+use my_first_lisp::eval::EvalError;
+
+use common::{eval, eval_err};
+
+#[test]
+fn lists_are_built_and_taken_apart() {
+    assert_eq!(eval("(list 1 2 3)").unwrap(), "(1 2 3)");
+    assert_eq!(eval("(list)").unwrap(), "()");
+    assert_eq!(eval("(car (list 1 2 3))").unwrap(), "1");
+    assert_eq!(eval("(cdr (list 1 2 3))").unwrap(), "(2 3)");
+    assert_eq!(eval("(cdr (list 1))").unwrap(), "()");
+    assert_eq!(eval_err("(car (list))").to_string(), "Cannot apply 'car' to an empty list");
+}
+
+#[test]
+fn comparisons_give_booleans() {
+    assert_eq!(eval("(> 5 3)").unwrap(), "true");
+    assert_eq!(eval("(< 5 3)").unwrap(), "false");
+    assert_eq!(eval("(<= 2 2)").unwrap(), "true");
+    assert_eq!(eval("(>= 1 2)").unwrap(), "false");
+}
+
+#[test]
+fn comparisons_chain_across_all_arguments() {
+    assert_eq!(eval("(< 1 2 3)").unwrap(), "true");
+    assert_eq!(eval("(< 1 3 2)").unwrap(), "false");
+    assert_eq!(eval("(<= 1 1 2 2)").unwrap(), "true");
+    assert_eq!(eval("(> 4 3 2 1)").unwrap(), "true");
+    assert_eq!(eval("(>= 4 3 3 5)").unwrap(), "false");
+}
+
+#[test]
+fn comparison_chains_stop_at_the_first_failure() {
+    assert_eq!(eval("(< 2 1 nope)").unwrap(), "false");
+    assert!(eval("(< 1 2 nope)").is_err());
+}
+
+#[test]
+fn comparison_chains_reject_mixed_types() {
+    assert_eq!(eval_err("(< 1 2 2.5)").to_string(), "Cannot apply '<' to non-integer '2.5'");
+}
+
+#[test]
+fn logic_builtins() {
+    assert_eq!(eval("(and true true)").unwrap(), "true");
+    assert_eq!(eval("(and true false)").unwrap(), "false");
+    assert_eq!(eval("(or false true)").unwrap(), "true");
+    assert_eq!(eval("(or false false)").unwrap(), "false");
+    assert_eq!(eval("(not true)").unwrap(), "false");
+}
+
+#[test]
+fn and_and_or_short_circuit() {
+    assert_eq!(eval("(and false nope)").unwrap(), "false");
+    assert_eq!(eval("(or true nope)").unwrap(), "true");
+    assert!(eval("(and true nope)").is_err());
+    assert!(eval("(or false nope)").is_err());
+}
+
+#[test]
+fn mod_gives_the_remainder() {
+    assert_eq!(eval("(mod 10 3)").unwrap(), "1");
+    assert_eq!(eval("(mod -7 2)").unwrap(), "-1");
+    assert_eq!(eval_err("(mod 10 0)"), EvalError::DivisionByZero);
+}
+
+#[test]
+fn dividing_by_zero_is_an_error() {
+    assert_eq!(eval_err("(/ 5 0)"), EvalError::DivisionByZero);
+    assert_eq!(eval("(/ 5 0.0)").unwrap(), "inf");
+}
+
+#[test]
+fn mixed_arithmetic_promotes_to_float() {
+    assert_eq!(eval("(+ 1 2.5)").unwrap(), "3.5");
+    assert_eq!(eval("(type (+ 1 2.0))").unwrap(), "float");
+    assert_eq!(eval("(* 2 2.5)").unwrap(), "5");
+    assert_eq!(eval("(type (* 2 2.5))").unwrap(), "float");
+}
+
+#[test]
+fn integer_arithmetic_stays_integer() {
+    assert_eq!(eval("(+ 1 2)").unwrap(), "3");
+    assert_eq!(eval("(type (* 2 3))").unwrap(), "integer");
+    assert_eq!(eval("(type (- 7 2))").unwrap(), "integer");
+}
+
+#[test]
+fn reduce_folds_a_list() {
+    assert_eq!(eval("(reduce + 0 (list 1 2 3 4))").unwrap(), "10");
+    assert_eq!(eval("(reduce (fn (acc x) (cons x acc)) (list) (list 1 2 3))").unwrap(), "(3 2 1)");
+    assert_eq!(eval("(reduce + 5 (list))").unwrap(), "5");
+}
+
+#[test]
+fn map_applies_a_function_to_each_element() {
+    assert_eq!(eval("(map str-length (list \"a\" \"bb\"))").unwrap(), "(1 2)");
+    assert_eq!(eval("(map (fn (x) (* x x)) (list 1 2 3))").unwrap(), "(1 4 9)");
+    assert_eq!(eval("(map car (list))").unwrap(), "()");
+}
+
+#[test]
+fn filter_keeps_matching_elements() {
+    assert_eq!(eval("(filter number? (list 1 'a 2))").unwrap(), "(1 2)");
+    assert_eq!(eval("(filter (fn (x) (> x 1)) (list 1 2 3))").unwrap(), "(2 3)");
+    assert_eq!(eval("(filter (fn (x) (> x 5)) (list 1 2 3))").unwrap(), "()");
+}
+
+#[test]
+fn len_counts_list_elements_and_characters() {
+    assert_eq!(eval("(len (list 1 2 3))").unwrap(), "3");
+    assert_eq!(eval("(len (list))").unwrap(), "0");
+    assert_eq!(eval("(len \"héllo\")").unwrap(), "5");
+}
+
+#[test]
+fn nth_gives_an_element_by_index() {
+    assert_eq!(eval("(nth 0 (list 1 2 3))").unwrap(), "1");
+    assert_eq!(eval("(nth 2 (list 1 2 3))").unwrap(), "3");
+    assert_eq!(eval_err("(nth 5 (list 1 2))").to_string(), "Index 5 out of range for list of length 2");
+    assert_eq!(eval_err("(nth 0 (list))").to_string(), "Index 0 out of range for list of length 0");
+}
+
+#[test]
+fn append_joins_lists() {
+    assert_eq!(eval("(append (list 1 2) (list 3))").unwrap(), "(1 2 3)");
+    assert_eq!(eval("(append (list 1))").unwrap(), "(1)");
+    assert_eq!(eval("(append)").unwrap(), "()");
+    assert_eq!(eval("(append (list) (list))").unwrap(), "()");
+}
+
+#[test]
+fn lists_are_equal_element_by_element() {
+    assert_eq!(eval("(= (list 1 2) (list 1 2))").unwrap(), "true");
+    assert_eq!(eval("(= (list 1 2) (list 1 2 3))").unwrap(), "false");
+    assert_eq!(eval("(= (list 1 (list 2 3)) (list 1 (list 2 3)))").unwrap(), "true");
+    assert_eq!(eval("(= (list 1 (list 2 3)) (list 1 (list 2 4)))").unwrap(), "false");
+    assert_eq!(eval("(= (list 1) (list \"1\"))").unwrap(), "false");
+}
+
+#[test]
+fn not_equals() {
+    assert_eq!(eval("(!= 1 2)").unwrap(), "true");
+    assert_eq!(eval("(!= 1 1)").unwrap(), "false");
+    assert_eq!(eval("(!= (list 1) (list 2))").unwrap(), "true");
+}
+
+#[test]
+fn type_names_every_kind_of_value() {
+    for (src, name) in [
+        ("nil", "nil"),
+        ("true", "bool"),
+        ("1", "integer"),
+        ("1/2", "rational"),
+        ("1.5", "float"),
+        ("\"s\"", "string"),
+        ("\\a", "char"),
+        ("'x", "symbol"),
+        ("(list)", "list"),
+        ("(cons 1 2)", "pair"),
+        ("(hash-map)", "map"),
+        ("(bytes)", "bytes"),
+        ("(delay 1)", "promise"),
+        ("+", "function"),
+        ("(fn () 1)", "function"),
+    ] {
+        assert_eq!(eval(&format!("(type {})", src)).unwrap(), name, "{}", src);
+    }
+}
+
+#[test]
+fn predicates_test_the_type_of_a_value() {
+    for (predicate, yes, no) in [
+        ("nil?", "nil", "0"),
+        ("bool?", "false", "nil"),
+        ("number?", "1.5", "\"1\""),
+        ("number?", "1/2", "'x"),
+        ("list?", "(list)", "(cons 1 2)"),
+        ("fn?", "car", "'car"),
+        ("fn?", "(fn () 1)", "(list)"),
+    ] {
+        assert_eq!(eval(&format!("({} {})", predicate, yes)).unwrap(), "true", "{} {}", predicate, yes);
+        assert_eq!(eval(&format!("({} {})", predicate, no)).unwrap(), "false", "{} {}", predicate, no);
+    }
+}
+
+#[test]
+fn string_builtins_count_characters_not_bytes() {
+    assert_eq!(eval("(str-concat \"hé\" \"llo\" \"\")").unwrap(), "héllo");
+    assert_eq!(eval("(str-length \"héllo\")").unwrap(), "5");
+    assert_eq!(eval("(str-length \"\")").unwrap(), "0");
+    assert_eq!(eval("(substring \"héllo\" 1 3)").unwrap(), "él");
+    assert!(eval("(substring \"héllo\" 3 9)").is_err());
+}
+
+#[test]
+fn numbers_convert_to_and_from_strings() {
+    assert_eq!(eval("(= (number->str 42) \"42\")").unwrap(), "true");
+    assert_eq!(eval("(= (str->number \"3.14\") 3.14)").unwrap(), "true");
+    assert_eq!(eval("(type (str->number \"3.14\"))").unwrap(), "float");
+    assert_eq!(eval("(type (str->number \"42\"))").unwrap(), "integer");
+    assert_eq!(eval_err("(str->number \"abc\")").to_string(), "Cannot parse 'abc' as a number");
+}
+
+#[test]
+fn read_file_gives_the_contents_of_a_file() {
+    let path = std::env::temp_dir().join(format!("my-first-lisp-read-{}.txt", process::id()));
+    fs::write(&path, "héllo\nworld\n").unwrap();
+    let res = eval(&format!("(read-file {:?})", path.to_str().unwrap()));
+    fs::remove_file(&path).unwrap();
+    assert_eq!(res.unwrap(), "héllo\nworld\n");
+    assert!(eval_err("(read-file \"/no/such/file\")").to_string().starts_with("Cannot read '/no/such/file'"));
+}
+
+#[test]
+fn integer_overflow_is_an_error() {
+    assert_eq!(eval_err("(* 9223372036854775807 2)"), EvalError::IntegerOverflow);
+    assert_eq!(eval_err("(- -9223372036854775807 2)"), EvalError::IntegerOverflow);
+    assert_eq!(eval_err("(+ 4611686018427387904 4611686018427387904)"), EvalError::IntegerOverflow);
+    assert_eq!(eval("(* 3037000499 3037000499)").unwrap(), "9223372030926249001");
+}
+
+#[test]
+fn apply_calls_a_function_with_a_list_of_arguments() {
+    assert_eq!(eval("(apply + (list 1 2 3))").unwrap(), "6");
+    assert_eq!(eval("(apply + 1 2 (list 3 4))").unwrap(), "10");
+    assert_eq!(eval("(apply (fn (a b) (- a b)) (list 5 3))").unwrap(), "2");
+}
+
+#[test]
+fn min_and_max() {
+    assert_eq!(eval("(max 3 7 2)").unwrap(), "7");
+    assert_eq!(eval("(min 3 7 2)").unwrap(), "2");
+    assert_eq!(eval("(max 1 2.5)").unwrap(), "2.5");
+}
+
+#[test]
+fn rounding_builtins() {
+    assert_eq!(eval("(abs -5)").unwrap(), "5");
+    assert_eq!(eval("(abs -2.5)").unwrap(), "2.5");
+    assert_eq!(eval("(floor 2.7)").unwrap(), "2");
+    assert_eq!(eval("(ceil 2.1)").unwrap(), "3");
+    assert_eq!(eval("(round 2.5)").unwrap(), "3");
+    assert_eq!(eval("(round -2.5)").unwrap(), "-3");
+}
+
+#[test]
+fn math_builtins_give_floats() {
+    assert_eq!(eval("(sqrt 4)").unwrap(), "2");
+    assert_eq!(eval("(type (sqrt 4))").unwrap(), "float");
+    assert_eq!(eval("(pow 2 10)").unwrap(), "1024");
+    assert_eq!(eval("(sin 0)").unwrap(), "0");
+    assert_eq!(eval("(cos 0)").unwrap(), "1");
+    assert_eq!(eval("(log 1)").unwrap(), "0");
+    assert_eq!(eval_err("(sqrt -1)").to_string(), "'sqrt' is undefined for -1");
+}
+
+#[test]
+fn pi_and_e_are_predefined() {
+    assert_eq!(eval("(< 3.14159 pi 3.14160)").unwrap(), "true");
+    assert_eq!(eval("(< 6.28318 (* 2 pi) 6.28319)").unwrap(), "true");
+    assert_eq!(eval("(< 2.71828 e 2.71829)").unwrap(), "true");
+}
+
+#[test]
+fn time_gives_the_value() {
+    assert_eq!(eval("(time (+ 1 2))").unwrap(), "3");
+}
+
+#[test]
+fn rationals_are_exact() {
+    assert_eq!(eval("(+ 1/2 1/3)").unwrap(), "5/6");
+    assert_eq!(eval("(= (+ 1/2 1/3) 5/6)").unwrap(), "true");
+    assert_eq!(eval("(- 1/2 1/6)").unwrap(), "1/3");
+    assert_eq!(eval("(* 1/2 2)").unwrap(), "1");
+    assert_eq!(eval("(type (* 1/2 2))").unwrap(), "integer");
+    assert_eq!(eval("2/4").unwrap(), "1/2");
+}
+
+#[test]
+fn integers_and_rationals_compare_as_exact_numbers() {
+    assert_eq!(eval("(= 1/2 1)").unwrap(), "false");
+    assert_eq!(eval("(= (* 1/3 2) 1)").unwrap(), "false");
+    assert_eq!(eval("(= (* 1/3 3) 1)").unwrap(), "true");
+    assert_eq!(eval("(!= 1 1/2)").unwrap(), "true");
+    assert_eq!(eval("(case (* 1/3 2) (1 'one) (2/3 'two-thirds) (else 'other))").unwrap(), "two-thirds");
+    assert_eq!(eval("(case (+ 1/2 1/3) (1 'one) (else 'other))").unwrap(), "other");
+}
+
+#[test]
+fn unary_minus_negates_and_unary_divide_inverts() {
+    assert_eq!(eval("(- 5)").unwrap(), "-5");
+    assert_eq!(eval("(- 1/2)").unwrap(), "-1/2");
+    assert_eq!(eval("(- 2.5)").unwrap(), "-2.5");
+    assert_eq!(eval("(/ 2)").unwrap(), "1/2");
+    assert_eq!(eval("(/ -2/3)").unwrap(), "-3/2");
+    assert_eq!(eval("(/ 4.0)").unwrap(), "0.25");
+    assert_eq!(eval_err("(/ 0)"), EvalError::DivisionByZero);
+    assert_eq!(eval_err("(- (- -9223372036854775807 1))"), EvalError::IntegerOverflow);
+}
+
+#[test]
+fn maps_are_built_and_looked_up() {
+    let m = "(def m (hash-map :a 1 \"b\" (list 2))) ";
+    assert_eq!(eval(&format!("{}m", m)).unwrap(), "{:a 1, b (2)}");
+    assert_eq!(eval(&format!("{}(hash-get m :a)", m)).unwrap(), "1");
+    assert_eq!(eval(&format!("{}(hash-get m \"b\")", m)).unwrap(), "(2)");
+    assert_eq!(eval(&format!("{}(hash-get m :c)", m)).unwrap(), "nil");
+    assert_eq!(eval(&format!("{}(hash-get m :c 0)", m)).unwrap(), "0");
+    assert_eq!(eval_err("(hash-map :a)").to_string(), "'hash-map' takes an even number of arguments");
+}
+
+#[test]
+fn hash_set_leaves_the_original_map_alone() {
+    let src = "(def m (hash-map :a 1)) (def n (hash-set m :a 2)) (list (hash-get m :a) (hash-get n :a))";
+    assert_eq!(eval(src).unwrap(), "(1 2)");
+}
+
+#[test]
+fn format_fills_placeholders_in_turn() {
+    assert_eq!(eval("(format \"x={} y={}\" 1 2)").unwrap(), "x=1 y=2");
+    assert_eq!(eval("(format \"{} and {}\" (list 1) \"s\")").unwrap(), "(1) and s");
+    assert_eq!(eval("(format \"none\")").unwrap(), "none");
+    assert!(eval("(format \"{} {}\" 1)").is_err());
+}
+
+#[test]
+fn sort_orders_lists() {
+    assert_eq!(eval("(sort (list 3 1 2))").unwrap(), "(1 2 3)");
+    assert_eq!(eval("(sort (list 3 1 2) >)").unwrap(), "(3 2 1)");
+    assert_eq!(eval("(sort (list 3 1 2) (fn (a b) (> a b)))").unwrap(), "(3 2 1)");
+    assert_eq!(eval("(sort (list))").unwrap(), "()");
+}
+
+#[test]
+fn bytes_are_built_and_indexed() {
+    assert_eq!(eval("(bytes 1 2 255)").unwrap(), "#bytes[1 2 255]");
+    assert_eq!(eval("(byte-ref (bytes 1 2 3) 1)").unwrap(), "2");
+    assert_eq!(eval("(bytes-length (bytes 1 2 3))").unwrap(), "3");
+    assert_eq!(eval_err("(bytes 1 2 256)").to_string(), "Byte '256' in 'bytes' must be an integer from 0 to 255");
+    assert!(eval("(byte-ref (bytes 1) 1)").is_err());
+}
+
+#[test]
+fn numbers_coerce_between_integer_and_float() {
+    assert_eq!(eval("(to-int 3.9)").unwrap(), "3");
+    assert_eq!(eval("(to-int -3.9)").unwrap(), "-3");
+    assert_eq!(eval("(to-int 7/2)").unwrap(), "3");
+    assert_eq!(eval("(= (to-float 5) 5.0)").unwrap(), "true");
+    assert_eq!(eval("(type (to-float 5))").unwrap(), "float");
+    assert!(eval("(to-int 1e300)").is_err());
+}
+
+#[test]
+fn bitwise_builtins() {
+    assert_eq!(eval("(bit-and 12 10)").unwrap(), "8");
+    assert_eq!(eval("(bit-or 12 10)").unwrap(), "14");
+    assert_eq!(eval("(bit-xor 12 10)").unwrap(), "6");
+    assert_eq!(eval("(bit-not 0)").unwrap(), "-1");
+    assert_eq!(eval("(bit-shift 1 4)").unwrap(), "16");
+    assert_eq!(eval("(bit-shift 16 -4)").unwrap(), "1");
+}
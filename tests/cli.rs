@@ -0,0 +1,119 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the interpreter with the given arguments, feeding it `stdin`.
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_my-first-lisp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str], stdin: &str) -> String {
+    let output = run(args, stdin);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn eval_prints_the_value() {
+    assert_eq!(stdout(&["eval", "(+ 1 2)"], ""), "3\n");
+}
+
+#[test]
+fn eval_accepts_negative_numbers() {
+    assert_eq!(stdout(&["eval", "-5"], ""), "-5\n");
+    assert_eq!(stdout(&["eval", "-2.5"], ""), "-2.5\n");
+}
+
+#[test]
+fn repl_type_gives_the_type_without_keeping_definitions() {
+    let out = stdout(&["--no-emoji", "repl"], ":type 1.5\n:type (def x 1)\n:type x\n");
+    assert!(out.contains("=> float\n=> nil\nERROR: Unknown symbol 'x'\n"), "{}", out);
+}
+
+#[test]
+fn repl_type_without_an_expression_is_a_usage_error() {
+    let out = stdout(&["--no-emoji", "repl"], ":type\n:type   \n");
+    assert_eq!(out.matches("ERROR: usage: :type <expr>\n").count(), 2, "{}", out);
+    assert!(!out.contains("=>"), "{}", out);
+}
+
+#[test]
+fn repl_carries_on_after_an_error() {
+    let out = stdout(&["--no-emoji", "repl"], "(/ 5 0)\n(+ 1 2)\n");
+    assert!(out.contains("ERROR: Division by zero\n=> 3\n"), "{}", out);
+}
+
+#[test]
+fn printed_output_goes_to_stdout_unstyled() {
+    assert_eq!(stdout(&["eval", "(println 1 2 3)"], ""), "1 2 3\nnil\n");
+}
+
+#[test]
+fn version_flag_prints_the_version() {
+    assert_eq!(stdout(&["--version"], ""), format!("my-first-lisp {}\n", env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn no_emoji_flag_changes_the_output() {
+    let fancy = stdout(&["repl"], "(/ 1 0)\n");
+    let plain = stdout(&["--no-emoji", "repl"], "(/ 1 0)\n");
+    assert_ne!(fancy, plain);
+    assert!(plain.contains("ERROR: Division by zero"), "{}", plain);
+    assert!(plain.is_ascii(), "{}", plain);
+}
+
+#[test]
+fn run_reads_a_script_from_stdin() {
+    assert_eq!(stdout(&["run", "-"], "(def x 2)\n(println (* x 21))\n"), "42\n");
+}
+
+#[test]
+fn time_reports_on_stderr() {
+    let output = run(&["eval", "(time (+ 1 2))"], "");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("Elapsed time: "));
+}
+
+#[test]
+fn scripts_may_start_with_a_shebang() {
+    let path = std::env::temp_dir().join(format!("my-first-lisp-shebang-{}.lisp", std::process::id()));
+    fs::write(&path, "#!/usr/bin/env -S my-first-lisp run\n(println (+ 1 2))\n").unwrap();
+    let out = stdout(&["run", path.to_str().unwrap()], "");
+    fs::remove_file(&path).unwrap();
+    assert_eq!(out, "3\n");
+}
+
+#[test]
+fn repl_load_evaluates_a_file() {
+    let path = std::env::temp_dir().join(format!("my-first-lisp-repl-load-{}.lisp", std::process::id()));
+    fs::write(&path, "(defn triple (x) (* x 3))\n").unwrap();
+    let path = path.to_str().unwrap();
+    let out = stdout(&["--no-emoji", "repl"], &format!(":load {}\n(triple 4)\n:load\n", path));
+    fs::remove_file(path).unwrap();
+    assert!(out.contains(&format!("loaded {}\n=> 12\nERROR: usage: :load <path>\n", path)), "{}", out);
+}
+
+#[test]
+fn repl_ignores_comment_only_lines() {
+    let out = run(&["--no-emoji", "repl"], "; just a comment\n   ; indented\n(+ 1 2) ; trailing\n");
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(!stdout.contains("ERROR"), "{}", stdout);
+    assert_eq!(stdout.matches("=>").count(), 1, "{}", stdout);
+    assert!(stdout.contains("=> 3\n"), "{}", stdout);
+}
+
+#[test]
+fn repl_env_lists_bindings() {
+    let out = stdout(&["--no-emoji", "repl"], "(def x 1)\n(defn f (a) a)\n:env\n");
+    assert!(out.contains("  x : integer = 1\n"), "{}", out);
+    assert!(out.contains("  f : user-defined function\n"), "{}", out);
+    assert!(out.contains("  car : built-in function 'car'\n"), "{}", out);
+}
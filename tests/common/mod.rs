@@ -0,0 +1,37 @@
+// each test crate uses only some of these
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use my_first_lisp::eval::{Env, EvalError};
+use my_first_lisp::eval_str;
+
+/// Evaluates a script in a fresh environment, giving its value as displayed.
+pub fn eval(src: &str) -> Result<String, EvalError> {
+    eval_str(src, &mut Env::default()).map(|value| value.to_string())
+}
+
+/// Evaluates a script that's expected to fail, giving the error.
+pub fn eval_err(src: &str) -> EvalError {
+    match eval(src) {
+        Ok(value) => panic!("expected {:?} to fail, but it gave {}", src, value),
+        Err(err) => err,
+    }
+}
+
+/// Evaluates a script in a fresh environment, giving everything it printed.
+pub fn output(src: &str) -> String {
+    output_in(src, Env::default())
+}
+
+/// Evaluates a script in the given environment, giving everything it printed.
+pub fn output_in(src: &str, env: Env) -> String {
+    let out = Rc::new(RefCell::new(Vec::new()));
+    let mut env = env.with_writer(out.clone());
+    if let Err(err) = eval_str(src, &mut env) {
+        panic!("{:?} failed: {}", src, err);
+    }
+    let bytes = out.borrow().clone();
+    String::from_utf8(bytes).unwrap()
+}
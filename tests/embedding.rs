@@ -0,0 +1,79 @@
+mod common;
+
+use std::rc::Rc;
+
+use my_first_lisp::eval::{Env, Value};
+use my_first_lisp::{eval_script, eval_str, parser};
+
+use common::eval;
+
+#[test]
+fn eval_str_gives_the_value_of_the_last_expression() {
+    assert_eq!(eval("(+ 1 2)").unwrap(), "3");
+    assert_eq!(eval("1 2 (list 3 4)").unwrap(), "(3 4)");
+    assert_eq!(eval("\"hi\"").unwrap(), "hi");
+}
+
+#[test]
+fn empty_script_is_nil() {
+    assert_eq!(eval("").unwrap(), "nil");
+    assert_eq!(eval("; just a comment").unwrap(), "nil");
+}
+
+#[test]
+fn definitions_persist_in_the_environment_between_calls() {
+    let mut env = Env::default();
+    eval_str("(def x 20)", &mut env).unwrap();
+    let res = eval_str("(+ x 22)", &mut env).unwrap();
+    assert_eq!(*res, Value::Integer(42));
+}
+
+#[test]
+fn parse_errors_are_reported_as_errors() {
+    let err = eval("(+ 1").unwrap_err().to_string();
+    assert!(err.contains("unexpected end of input"), "{}", err);
+}
+
+#[test]
+fn eval_script_evaluates_a_parsed_script() {
+    let script = parser::ScriptParser::new().parse("(def y 2) (* y 3)").unwrap();
+    let res = eval_script(&script, &mut Env::default()).unwrap();
+    assert_eq!(*res, Value::Integer(6));
+}
+
+#[test]
+fn values_compare_as_the_equals_builtin_does() {
+    let mut env = Env::default();
+    let mut value = |src: &str| eval_str(src, &mut env).unwrap();
+    assert_eq!(value("(+ 1 2)"), Rc::new(Value::Integer(3)));
+    assert_ne!(value("3"), value("3.0"));
+    assert_eq!(value("(= 3 (+ 1 2))"), Rc::new(Value::Bool(true)));
+    assert_eq!(value("(= true true)"), Rc::new(Value::Bool(true)));
+    assert_eq!(value("(list 1 (list 2))"), value("'(1 (2))"));
+    assert_eq!(value("(= (list 1 (list 2)) '(1 (2)))"), Rc::new(Value::Bool(true)));
+    assert_ne!(value("(list 1 2)"), value("(list 1 2 3)"));
+    assert_ne!(value("car"), value("car"));
+}
+
+#[test]
+fn cloned_environments_keep_closures_working() {
+    let mut env = Env::default();
+    eval_str("(def make-adder (fn (x) (fn (y) (+ x y)))) (def add3 (make-adder 3))", &mut env).unwrap();
+    let mut clone = env.clone();
+    assert_eq!(*eval_str("(add3 4)", &mut clone).unwrap(), Value::Integer(7));
+    // the clone shares its bindings with the original
+    eval_str("(def z 1)", &mut clone).unwrap();
+    assert_eq!(*eval_str("z", &mut env).unwrap(), Value::Integer(1));
+}
+
+#[test]
+fn restoring_a_snapshot_undoes_definitions() {
+    let mut env = Env::default();
+    eval_str("(def x 1)", &mut env).unwrap();
+    let snapshot = env.snapshot();
+    eval_str("(def y 2) (set! x 3)", &mut env).unwrap();
+    assert!(env.changed_since(&snapshot));
+    env.restore(snapshot);
+    assert!(eval_str("y", &mut env).is_err());
+    assert_eq!(*eval_str("x", &mut env).unwrap(), Value::Integer(1));
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use my_first_lisp::eval::EvalError;
+
+use common::eval_err;
+
+fn type_mismatch(name: &str, expected: &'static str, found: &str) -> EvalError {
+    EvalError::TypeMismatch { name: name.to_string(), expected, found: found.to_string() }
+}
+
+fn arity(name: &str, expected: &str) -> EvalError {
+    EvalError::Arity { name: name.to_string(), expected: expected.to_string() }
+}
+
+#[test]
+fn unknown_symbols_are_named() {
+    assert_eq!(eval_err("(+ 1 nope)"), EvalError::UnknownSymbol("nope".into()));
+}
+
+#[test]
+fn arithmetic_on_non_numbers_is_a_type_mismatch() {
+    assert_eq!(eval_err("(+ 1 \"a\")"), type_mismatch("+", "number", "a"));
+    assert_eq!(eval_err("(* 2 'x)"), type_mismatch("*", "number", "x"));
+    assert_eq!(eval_err("(sqrt true)"), type_mismatch("sqrt", "number", "true"));
+    assert_eq!(eval_err("(mod 7 2.5)"), type_mismatch("mod", "integer", "2.5"));
+    assert_eq!(eval_err("(mod 7.5 2)"), type_mismatch("mod", "integer", "7.5"));
+}
+
+#[test]
+fn comparing_mismatched_values_is_a_type_mismatch() {
+    assert_eq!(eval_err("(< 1 2.5)"), type_mismatch("<", "integer", "2.5"));
+    assert_eq!(eval_err("(>= 1.5 1)"), type_mismatch(">=", "float", "1"));
+    assert_eq!(eval_err("(< \"a\" \"b\")"), type_mismatch("<", "number", "a"));
+    assert_eq!(eval_err("(= 1 \"1\")"), type_mismatch("=", "integer", "1"));
+    assert_eq!(eval_err("(case 1 (\"1\" 'one))"), type_mismatch("case", "integer", "1"));
+}
+
+#[test]
+fn logic_and_strings_check_their_argument_types() {
+    assert_eq!(eval_err("(and true 1)"), type_mismatch("and", "bool", "1"));
+    assert_eq!(eval_err("(or false nil)"), type_mismatch("or", "bool", "nil"));
+    assert_eq!(eval_err("(str-concat \"a\" 1)"), type_mismatch("str-concat", "string", "1"));
+}
+
+#[test]
+fn list_functions_check_their_argument_types() {
+    assert_eq!(eval_err("(append (list 1) 2)"), type_mismatch("append", "list", "2"));
+    assert_eq!(eval_err("(nth 'a (list 1))"), type_mismatch("nth", "integer", "a"));
+    assert_eq!(eval_err("(len 5)"), type_mismatch("len", "collection", "5"));
+    assert_eq!(eval_err("(sort 5)"), type_mismatch("sort", "list", "5"));
+    assert_eq!(eval_err("(sort (list 1 \"a\"))"), type_mismatch("sort", "string", "1"));
+    assert_eq!(eval_err("(sort (list 2 1) (fn (a b) 1))"), type_mismatch("sort", "bool", "1"));
+    assert_eq!(eval_err("(map car 5)"), type_mismatch("map", "list", "5"));
+    assert_eq!(eval_err("(filter car 5)"), type_mismatch("filter", "list", "5"));
+    assert_eq!(eval_err("(filter (fn (x) x) (list 1))"), type_mismatch("filter", "bool", "1"));
+    assert_eq!(eval_err("(reduce + 0 5)"), type_mismatch("reduce", "list", "5"));
+}
+
+#[test]
+fn calling_a_non_function_is_a_type_mismatch() {
+    assert_eq!(eval_err("(def x 5) (x 1)"), type_mismatch("x", "function", "5"));
+    assert_eq!(eval_err("(map 5 (list 1))"), type_mismatch("apply", "function", "5"));
+    assert_eq!(eval_err("(apply 5 (list 1))"), type_mismatch("apply", "function", "5"));
+    assert_eq!(eval_err("(apply + 5)"), type_mismatch("apply", "list", "5"));
+    assert_eq!(eval_err("(apply +)"), arity("apply", "at least 2 arguments"));
+}
+
+#[test]
+fn builtins_given_the_wrong_number_of_arguments_report_arity() {
+    assert_eq!(eval_err("(car)"), arity("car", "1 argument only"));
+    assert_eq!(eval_err("(< 1)"), arity("<", "at least 2 arguments"));
+    assert_eq!(eval_err("(= 1)"), arity("=", "at least 2 arguments"));
+    assert_eq!(eval_err("(mod)"), arity("mod", "at least 1 argument"));
+}
+
+#[test]
+fn user_functions_given_the_wrong_number_of_arguments_report_arity() {
+    assert_eq!(eval_err("(defn f (a b) a) (f 1)"), arity("<fn (a b)>", "2 arguments only"));
+    assert_eq!(eval_err("(defn f (a) a) (f 1 2)"), arity("<fn (a)>", "1 argument only"));
+    assert_eq!(eval_err("(defn f () 1) (f 1)"), arity("<fn ()>", "no arguments"));
+    assert_eq!(eval_err("(defn f (a & rest) a) (f)"), arity("<fn (a & rest)>", "at least 1 argument"));
+    assert_eq!(eval_err("(defn f (a :b) a) (f 1 :b 2 3)").to_string(), "Positional arguments must come before keyword arguments");
+    assert_eq!(eval_err("(defn f (a :b) a) (f 1 2 3 :b 4)"), arity("<fn (a :b)>", "at most 2 arguments"));
+}
+
+#[test]
+fn arithmetic_errors_have_their_own_variants() {
+    assert_eq!(eval_err("(/ 1 0)"), EvalError::DivisionByZero);
+    assert_eq!(eval_err("(+ 9223372036854775807 1)"), EvalError::IntegerOverflow);
+}
+
+#[test]
+fn parse_errors_carry_their_position() {
+    let parse = |line, column, message: &str| EvalError::Parse { line, column, message: message.to_string() };
+    assert_eq!(eval_err("(+ 1"), parse(1, 5, "unexpected end of input"));
+    assert_eq!(eval_err("(def x 1)\n  (+ x 2))"), parse(2, 10, "unexpected token ')'"));
+    assert_eq!(eval_err("(list 1\n  \"a\\qb\")"), parse(2, 3, "Invalid escape sequence in string literal"));
+    assert_eq!(eval_err("(+ 1 99999999999999999999)"), parse(1, 6, "Integer literal out of range"));
+}
+
+#[test]
+fn errors_raised_by_programs_are_user_errors() {
+    assert_eq!(eval_err("(error \"oops\")"), EvalError::UserError("oops".to_string()));
+}
+
+#[test]
+fn type_mismatches_display_as_before() {
+    assert_eq!(eval_err("(+ 1 \"a\")").to_string(), "Cannot apply '+' to non-number 'a'");
+    assert_eq!(eval_err("(car)").to_string(), "'car' takes 1 argument only");
+}
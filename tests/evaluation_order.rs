@@ -0,0 +1,55 @@
+mod common;
+
+use common::eval;
+
+// `tick` records the order its arguments are evaluated in, by the counter
+// value at the time, e.g. (list (tick) (tick)) gives (1 2)
+const COUNTER: &str = "(def n 0) (defn tick () (set! n (+ n 1)) n) ";
+
+fn eval_counted(src: &str) -> String {
+    eval(&format!("{}{}", COUNTER, src)).unwrap()
+}
+
+#[test]
+fn builtin_arguments_are_evaluated_left_to_right() {
+    assert_eq!(eval_counted("(list (tick) (tick) (tick))"), "(1 2 3)");
+    assert_eq!(eval_counted("(- (tick) (tick))"), "-1");
+    assert_eq!(eval_counted("(< (tick) (tick))"), "true");
+}
+
+#[test]
+fn user_function_arguments_are_evaluated_left_to_right() {
+    assert_eq!(eval_counted("(defn pair (a b) (list a b)) (pair (tick) (tick))"), "(1 2)");
+    assert_eq!(eval_counted("((fn (& xs) xs) (tick) (tick) (tick))"), "(1 2 3)");
+}
+
+#[test]
+fn function_position_is_evaluated_before_the_arguments() {
+    assert_eq!(eval_counted("((begin (tick) list) (tick))"), "(2)");
+}
+
+#[test]
+fn nth_evaluates_the_index_before_the_list() {
+    assert_eq!(eval_counted("(nth (- (tick) 1) (list (tick) (tick)))"), "2");
+}
+
+#[test]
+fn apply_evaluates_its_arguments_left_to_right() {
+    assert_eq!(eval_counted("(apply list (tick) (list (tick) (tick)))"), "(1 2 3)");
+    assert_eq!(eval_counted("(apply (begin (tick) list) (list (tick)))"), "(2)");
+}
+
+#[test]
+fn hash_set_evaluates_map_key_then_value() {
+    assert_eq!(
+        eval_counted("(def m (hash-set (begin (tick) (hash-map)) (tick) (tick))) (list (hash-get m 2) n)"),
+        "(3 3)",
+    );
+}
+
+#[test]
+fn each_argument_is_evaluated_once() {
+    assert_eq!(eval_counted("(list (tick) (tick)) n"), "2");
+    assert_eq!(eval_counted("(defn pair (a b) (list a b)) (pair (tick) (tick)) n"), "2");
+    assert_eq!(eval_counted("(apply list (list (tick))) n"), "1");
+}
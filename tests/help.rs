@@ -0,0 +1,29 @@
+mod common;
+
+use my_first_lisp::eval::{Env, Func, Value};
+
+use common::eval;
+
+#[test]
+fn help_names_builtins_by_their_symbol() {
+    assert_eq!(eval("(help +)").unwrap(), "built-in function '+': (+ n...) adds numbers");
+    assert!(eval("(help <=)").unwrap().starts_with("built-in function '<=': "));
+    assert!(eval("(help if)").unwrap().starts_with("built-in function 'if': "));
+    assert_eq!(eval("+").unwrap(), "<built-in function '+'>");
+}
+
+#[test]
+fn help_describes_user_defined_and_memoized_functions() {
+    assert_eq!(eval("(defn f (x y) x) (help f)").unwrap(), "user-defined function <fn (x y)>");
+    assert!(eval("(help (memoize +))").unwrap().starts_with("memoized built-in function '+': "));
+}
+
+#[test]
+fn every_builtin_is_named_after_its_symbol_and_documented() {
+    for (sym, value) in Env::default().bindings() {
+        if let Value::Func(Func::BuiltIn { name, doc, .. } | Func::SpecialForm { name, doc, .. }) = value.as_ref() {
+            assert_eq!(*name, &*sym);
+            assert!(!doc.is_empty(), "{} has no docstring", sym);
+        }
+    }
+}
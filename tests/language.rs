@@ -0,0 +1,199 @@
+mod common;
+
+use std::{fs, process};
+
+use my_first_lisp::eval::EvalError;
+
+use common::{eval, eval_err, output};
+
+#[test]
+fn closures_capture_their_defining_scope() {
+    let src = "(def make-adder (fn (x) (fn (y) (+ x y)))) ((make-adder 3) 4)";
+    assert_eq!(eval(src).unwrap(), "7");
+    let src = "(def add3 ((fn (x) (fn (y) (+ x y))) 3)) (def x 100) (add3 4)";
+    assert_eq!(eval(src).unwrap(), "7");
+}
+
+#[test]
+fn quote_gives_its_argument_unevaluated() {
+    assert_eq!(eval("(quote (1 2 x))").unwrap(), "(1 2 x)");
+    assert_eq!(eval("(quote x)").unwrap(), "x");
+    assert_eq!(eval("'(1 2 x)").unwrap(), "(1 2 x)");
+    assert_eq!(eval("'x").unwrap(), "x");
+    assert_eq!(eval("(car '(a b))").unwrap(), "a");
+    assert_eq!(eval("''x").unwrap(), "(quote x)");
+}
+
+#[test]
+fn side_effecting_forms_give_nil() {
+    assert_eq!(eval("(def x 1)").unwrap(), "nil");
+    assert_eq!(eval("(= nil nil)").unwrap(), "true");
+    assert_eq!(eval("(= (def x 1) nil)").unwrap(), "true");
+}
+
+#[test]
+fn nil_is_rejected_by_arithmetic_and_comparison() {
+    assert_eq!(eval_err("(+ 1 nil)").to_string(), "Cannot apply '+' to non-number 'nil'");
+    assert_eq!(eval_err("(= nil 1)").to_string(), "Cannot apply '=' to non-nil '1'");
+}
+
+#[test]
+fn begin_evaluates_in_sequence() {
+    assert_eq!(eval("(begin (def x 1) (def y 2) (+ x y))").unwrap(), "3");
+    assert_eq!(eval("(do (def x 1) (+ x 1))").unwrap(), "2");
+    assert_eq!(eval("(begin)").unwrap(), "nil");
+}
+
+#[test]
+fn function_bodies_may_have_several_expressions() {
+    assert_eq!(eval("(def f (fn (x) (def y (* x 2)) (+ y 1))) (f 3)").unwrap(), "7");
+}
+
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    let src = "(def count (fn (n) (if (= n 0) 'done (count (- n 1))))) (count 1000000)";
+    assert_eq!(eval(src).unwrap(), "done");
+}
+
+#[test]
+fn cond_evaluates_the_first_true_clause() {
+    let src = "(def x 5) (cond ((< x 0) 'negative) ((< x 10) 'small) (else 'large))";
+    assert_eq!(eval(src).unwrap(), "small");
+    assert_eq!(eval("(cond (false 1))").unwrap(), "nil");
+}
+
+#[test]
+fn cond_tests_must_be_booleans() {
+    assert_eq!(eval_err("(cond (1 2))").to_string(), "Test in 'cond' must evaluate to a boolean value");
+}
+
+#[test]
+fn rest_parameters_collect_extra_arguments() {
+    assert_eq!(eval("((fn (x & xs) xs) 1 2 3)").unwrap(), "(2 3)");
+    assert_eq!(eval("((fn (x & xs) xs) 1)").unwrap(), "()");
+    assert_eq!(eval("((fn (& xs) xs))").unwrap(), "()");
+    assert!(eval("((fn (x & xs) xs))").is_err());
+}
+
+#[test]
+fn while_loops_until_its_condition_fails() {
+    let src = "(def i 1) (def sum 0) (while (<= i 5) (set! sum (+ sum i)) (set! i (+ i 1))) sum";
+    assert_eq!(eval(src).unwrap(), "15");
+    assert_eq!(eval("(while false 1)").unwrap(), "nil");
+}
+
+#[test]
+fn gensym_makes_distinct_symbols() {
+    assert_eq!(eval("(= (gensym) (gensym))").unwrap(), "false");
+    assert_eq!(eval("(type (gensym))").unwrap(), "symbol");
+    assert!(eval("(gensym \"tmp\")").unwrap().starts_with("tmp"));
+}
+
+#[test]
+fn set_updates_captured_bindings() {
+    let src = "(def make-counter (fn () (def n 0) (fn () (set! n (+ n 1)) n)))
+               (def counter (make-counter))
+               (counter) (counter)";
+    assert_eq!(eval(src).unwrap(), "2");
+    assert_eq!(eval_err("(set! nope 1)").to_string(), "Cannot set undefined symbol 'nope'");
+}
+
+#[test]
+fn load_evaluates_a_file_in_the_environment() {
+    let path = std::env::temp_dir().join(format!("my-first-lisp-load-{}.lisp", process::id()));
+    fs::write(&path, "(defn double (x) (* x 2))\n").unwrap();
+    let res = eval(&format!("(load {:?}) (double 21)", path.to_str().unwrap()));
+    fs::remove_file(&path).unwrap();
+    assert_eq!(res.unwrap(), "42");
+}
+
+#[test]
+fn dotimes_counts_from_zero() {
+    assert_eq!(eval("(def sum 0) (dotimes (i 5) (set! sum (+ sum i))) sum").unwrap(), "10");
+    assert_eq!(eval("(def sum 0) (dotimes (i 0) (set! sum 1)) sum").unwrap(), "0");
+}
+
+#[test]
+fn try_recovers_from_errors() {
+    assert_eq!(eval("(try (/ 1 0) 0)").unwrap(), "0");
+    assert_eq!(eval("(try (/ 1 0) msg msg)").unwrap(), "Division by zero");
+    assert_eq!(eval("(try (/ 4 2) 0)").unwrap(), "2");
+}
+
+#[test]
+fn error_fails_with_its_message() {
+    assert_eq!(eval_err("(error \"boom\")").to_string(), "boom");
+    assert_eq!(eval("(try (error \"boom\") msg msg)").unwrap(), "boom");
+}
+
+#[test]
+fn memoize_avoids_repeated_evaluation() {
+    let fib = "(def calls 0)
+               (def fib (FN (fn (n) (set! calls (+ calls 1)) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))))
+               (list (fib 20) calls)";
+    assert_eq!(eval(&fib.replace("(FN ", "(begin ")).unwrap(), "(6765 21891)");
+    assert_eq!(eval(&fib.replace("(FN ", "(memoize ")).unwrap(), "(6765 21)");
+}
+
+#[test]
+fn assert_fails_unless_true() {
+    assert_eq!(eval("(assert (= 1 1))").unwrap(), "nil");
+    assert_eq!(eval_err("(assert (= 1 2))").to_string(), "Assertion failed: (= 1 2)");
+    assert_eq!(eval_err("(assert false \"nope\")").to_string(), "nope");
+}
+
+#[test]
+fn named_let_loops() {
+    let src = "(let loop ((n 5) (acc 0)) (if (= n 0) acc (loop (- n 1) (+ acc n))))";
+    assert_eq!(eval(src).unwrap(), "15");
+    let src = "(let countdown ((n 3) (seen (list))) (if (= n 0) seen (countdown (- n 1) (cons n seen))))";
+    assert_eq!(eval(src).unwrap(), "(1 2 3)");
+}
+
+#[test]
+fn keyword_arguments_mix_with_positional_ones() {
+    let f = "(defn f (x :y :z) (list x y z)) ";
+    assert_eq!(eval(&format!("{}(f 1 2 3)", f)).unwrap(), "(1 2 3)");
+    assert_eq!(eval(&format!("{}(f 1 :z 3 :y 2)", f)).unwrap(), "(1 2 3)");
+    assert_eq!(eval(&format!("{}(f 1 2 :z 3)", f)).unwrap(), "(1 2 3)");
+    assert_eq!(eval_err(&format!("{}(f 1 :y 2)", f)).to_string(), "Missing argument ':z'");
+    assert_eq!(eval_err(&format!("{}(f 1 :y 2 :y 3 :z 4)", f)).to_string(), "Argument ':y' provided more than once");
+}
+
+#[test]
+fn defn_defines_a_function() {
+    assert_eq!(eval("(defn square (x) (* x x)) (square 5)").unwrap(), "25");
+    assert_eq!(eval("(defn square (x) (* x x))").unwrap(), "nil");
+}
+
+#[test]
+fn when_and_unless_run_their_body_conditionally() {
+    assert_eq!(eval("(when true 1 2)").unwrap(), "2");
+    assert_eq!(eval("(when false 1 2)").unwrap(), "nil");
+    assert_eq!(eval("(unless false 1 2)").unwrap(), "2");
+    assert_eq!(eval("(unless true 1)").unwrap(), "nil");
+    assert_eq!(output("(when false (print 1)) (unless true (print 2)) (when true (print 3))"), "3");
+}
+
+#[test]
+fn case_dispatches_on_a_value() {
+    let case = "(defn name (n) (case n (1 'one) (2 'two) (else 'many))) ";
+    assert_eq!(eval(&format!("{}(name 2)", case)).unwrap(), "two");
+    assert_eq!(eval(&format!("{}(name 5)", case)).unwrap(), "many");
+    assert_eq!(eval("(case 'b (a 1) (b 2))").unwrap(), "2");
+    assert_eq!(eval("(case 3 (1 'one))").unwrap(), "nil");
+}
+
+#[test]
+fn case_rejects_data_of_another_type() {
+    assert!(matches!(eval_err("(case 1 (\"1\" 'one))"), EvalError::TypeMismatch { .. }));
+}
+
+#[test]
+fn let_binds_in_parallel_and_let_star_in_sequence() {
+    let outer = "(def x 1) ";
+    assert_eq!(eval(&format!("{}(let ((x 2) (y x)) y)", outer)).unwrap(), "1");
+    assert_eq!(eval(&format!("{}(let* ((x 2) (y x)) y)", outer)).unwrap(), "2");
+    assert!(eval("(let ((a 1) (b a)) b)").is_err());
+    assert_eq!(eval("(let* ((a 1) (b (+ a 1))) b)").unwrap(), "2");
+}
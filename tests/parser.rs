@@ -0,0 +1,127 @@
+mod common;
+
+use std::collections::HashMap;
+
+use my_first_lisp::ast::Expr;
+use my_first_lisp::eval::{Env, Value};
+use my_first_lisp::{eval_str, parser};
+
+use common::{eval, eval_err};
+
+#[test]
+fn unterminated_lists_report_where_input_ended() {
+    assert_eq!(eval_err("(+ 1").to_string(), "parse error at line 1, column 5: unexpected end of input");
+    assert_eq!(eval_err("(+ 1\n  (* 2 3)").to_string(), "parse error at line 2, column 10: unexpected end of input");
+}
+
+#[test]
+fn unexpected_tokens_report_where_they_are() {
+    assert_eq!(eval_err("(+ 1 2))").to_string(), "parse error at line 1, column 8: unexpected token ')'");
+    assert_eq!(eval_err("(def x 1)\n  (+ x 2))").to_string(), "parse error at line 2, column 10: unexpected token ')'");
+}
+
+#[test]
+fn negative_number_literals() {
+    assert_eq!(eval("-5").unwrap(), "-5");
+    assert_eq!(eval("-2.5").unwrap(), "-2.5");
+    assert_eq!(eval("(+ -5 -2.5)").unwrap(), "-7.5");
+    assert_eq!(eval("(- 5 3)").unwrap(), "2");
+    assert_eq!(eval("(- 5)").unwrap(), "-5");
+}
+
+#[test]
+fn scientific_and_underscored_literals() {
+    assert_eq!(eval("1e3").unwrap(), "1000");
+    assert_eq!(eval("1.5e-2").unwrap(), "0.015");
+    assert_eq!(eval("1_000").unwrap(), "1000");
+    assert_eq!(eval("1_000.000_5").unwrap(), "1000.0005");
+}
+
+#[test]
+fn malformed_underscores_are_rejected() {
+    assert_eq!(eval_err("_5").to_string(), "Unknown symbol '_5'");
+    assert_eq!(eval_err("1__0").to_string(), "parse error at line 1, column 1: unexpected token '1__0'");
+    assert!(eval("1_").is_err());
+}
+
+#[test]
+fn hexadecimal_and_binary_literals() {
+    assert_eq!(eval("0xff").unwrap(), "255");
+    assert_eq!(eval("0XFF").unwrap(), "255");
+    assert_eq!(eval("0b101").unwrap(), "5");
+    assert_eq!(eval("-0x10").unwrap(), "-16");
+    assert_eq!(eval_err("0xg").to_string(), "parse error at line 1, column 1: unexpected token '0xg'");
+}
+
+#[test]
+fn character_literals() {
+    assert_eq!(eval("\\a").unwrap(), "a");
+    assert_eq!(eval("(type \\a)").unwrap(), "char");
+    assert_eq!(eval("(= \\a \\a)").unwrap(), "true");
+    assert_eq!(eval("(= \\a \\b)").unwrap(), "false");
+    assert_eq!(eval("(list \\space \\( \\é)").unwrap(), "(  ( é)");
+}
+
+#[test]
+fn multi_line_input_is_complete_once_parens_balance() {
+    let first = "(defn square (x)\n";
+    let second = "  (* x x))";
+    assert_eq!(parser::unclosed_parens(first), 1);
+    assert_eq!(parser::unclosed_parens(&format!("{}{}", first, second)), 0);
+    let mut env = Env::default();
+    eval_str(&format!("{}{}", first, second), &mut env).unwrap();
+    assert_eq!(*eval_str("(square 5)", &mut env).unwrap(), Value::Integer(25));
+}
+
+#[test]
+fn parens_in_strings_chars_and_comments_are_not_counted() {
+    assert_eq!(parser::unclosed_parens("(print \"(\" \\( ; (\n"), 1);
+    assert_eq!(parser::unclosed_parens("(a))"), -1);
+}
+
+#[test]
+fn true_and_false_parse_as_booleans() {
+    let script = parser::ScriptParser::new().parse("true false truer").unwrap();
+    assert_eq!(script.0, vec![Expr::Bool(true), Expr::Bool(false), Expr::Symbol("truer".into())]);
+    assert_eq!(eval_err("(def true 1)").to_string(), "First argument to 'def' must be a symbol");
+}
+
+#[test]
+fn identical_expressions_hash_and_compare_equal() {
+    let parse = |src: &str| parser::ExprParser::new().parse(src).unwrap();
+    let mut map = HashMap::new();
+    map.insert(parse("(a 1 \"s\" 2.5)"), 1);
+    assert_eq!(map.get(&parse("(a  1 \"s\"  2.5)")), Some(&1));
+    assert_eq!(map.get(&parse("(a 1 \"s\" 2.6)")), None);
+    map.insert(parse("(a 1 \"s\" 2.5)"), 2);
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn shebang_lines_are_stripped() {
+    assert_eq!(parser::strip_shebang("#!/usr/bin/env my-first-lisp run\n(+ 1 2)"), "\n(+ 1 2)");
+    assert_eq!(parser::strip_shebang("(+ 1 2)"), "(+ 1 2)");
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(eval(r#"(str-length "a\nb")"#).unwrap(), "3");
+    assert_eq!(eval(r#""a\nb\tc""#).unwrap(), "a\nb\tc");
+    assert_eq!(eval(r#""say \"hi\"""#).unwrap(), "say \"hi\"");
+    assert_eq!(eval(r#""back\\slash""#).unwrap(), "back\\slash");
+    assert_eq!(parser::unescape(r#"\\n"#).unwrap(), "\\n");
+}
+
+#[test]
+fn unknown_string_escapes_are_rejected() {
+    assert_eq!(parser::unescape(r"a\qb"), Err("Invalid escape sequence in string literal"));
+    assert_eq!(parser::unescape("trailing\\"), Err("Invalid escape sequence in string literal"));
+    assert!(eval_err(r#""a\qb""#).to_string().contains("Invalid escape sequence in string literal"));
+}
+
+#[test]
+fn comments_run_to_the_end_of_the_line() {
+    assert_eq!(eval("(+ 1 2) ; three").unwrap(), "3");
+    assert_eq!(eval("(list 1 ; one\n      2 ; two (\n      3)").unwrap(), "(1 2 3)");
+    assert_eq!(eval("\"; not a comment\"").unwrap(), "; not a comment");
+}
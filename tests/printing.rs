@@ -0,0 +1,142 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use my_first_lisp::ast::Expr;
+use my_first_lisp::eval::Env;
+use my_first_lisp::eval_str;
+
+use common::{eval, output, output_in};
+
+fn precise(digits: usize) -> Env<'static> {
+    Env::default().with_float_precision(Some(digits))
+}
+
+#[test]
+fn floats_print_in_full_by_default() {
+    assert_eq!(output("(println 3.14159 (/ 1.0 3))"), "3.14159 0.3333333333333333\n");
+    assert_eq!(output("(println 2.0)"), "2\n");
+}
+
+#[test]
+fn float_precision_rounds_printed_floats() {
+    assert_eq!(output_in("(println 3.14159)", precise(3)), "3.14\n");
+    assert_eq!(output_in("(print 1234567.0)", precise(2)), "1200000");
+    assert_eq!(output_in("(print 0.000123456)", precise(2)), "0.00012");
+}
+
+#[test]
+fn float_precision_reaches_inside_collections() {
+    assert_eq!(output_in("(println (list 1.23456 \"1.23456\" 7))", precise(2)), "(1.2 1.23456 7)\n");
+    assert_eq!(output_in("(println (hash-map :a 1.23456))", precise(2)), "{:a 1.2}\n");
+    assert_eq!(output_in("(println (cons 1.23456 2.34567))", precise(2)), "(1.2 . 2.3)\n");
+    assert_eq!(output_in("(pprint (list 1.23456))", precise(2)), "(1.2)\n");
+}
+
+#[test]
+fn whole_float_map_keys_keep_their_point() {
+    assert_eq!(eval("(hash-map 1.0 2 1 3)").unwrap(), "{1 3, 1.0 2}");
+    assert_eq!(eval("(hash-map 2.5 1)").unwrap(), "{2.5 1}");
+    assert_eq!(output_in("(println (hash-map 1.23456 1))", precise(2)), "{1.2 1}\n");
+    assert_eq!(output_in("(println (hash-map 1.0001 1))", precise(2)), "{1.0 1}\n");
+}
+
+#[test]
+fn float_precision_does_not_change_values_or_formatted_strings() {
+    let mut env = precise(2);
+    let value = eval_str("(* 3.14159 1)", &mut env).unwrap();
+    assert_eq!(value.to_string(), "3.14159");
+    assert_eq!(env.display(&value), "3.1");
+    assert_eq!(output_in("(println (format \"{}\" 3.14159))", precise(2)), "3.14159\n");
+    assert_eq!(output_in("(println (number->str 3.14159))", precise(2)), "3.14159\n");
+}
+
+#[test]
+fn float_precision_belongs_to_one_environment() {
+    assert_eq!(output_in("(println 3.14159)", precise(2)), "3.1\n");
+    assert_eq!(eval("3.14159").unwrap(), "3.14159");
+}
+
+#[test]
+fn println_separates_values_with_spaces() {
+    assert_eq!(output("(println 1 2 3)"), "1 2 3\n");
+    assert_eq!(output("(print \"a\" 'b) (print \\c)"), "a bc");
+    assert_eq!(output("(println)"), "\n");
+}
+
+#[test]
+fn debug_output_of_nested_lists() {
+    let value = eval_str("(list 1 (list \"a\" 'b))", &mut Env::default()).unwrap();
+    assert_eq!(format!("{:?}", value), r#"List([Integer(1), List([Str("a"), Symbol("b")])])"#);
+    let expr = Expr::List(vec![Expr::Integer(1), Expr::List(vec![Expr::Nil].into())].into());
+    assert_eq!(format!("{:?}", expr), "List([Integer(1), List([Nil])])");
+}
+
+#[test]
+fn lists_and_functions_display_distinctly() {
+    assert_eq!(eval("(list 1 2 3)").unwrap(), "(1 2 3)");
+    assert_eq!(eval("(list 1 (list) \"a b\")").unwrap(), "(1 () a b)");
+    assert_eq!(eval("car").unwrap(), "<built-in function 'car'>");
+    assert_eq!(eval("(fn (x) x)").unwrap(), "<fn (x)>");
+}
+
+#[test]
+fn pprint_breaks_long_lists_over_lines() {
+    let src = "(pprint '(define (long-function-name first-argument second-argument) (list first-argument second-argument)))";
+    let expected = "\
+(define
+ (long-function-name first-argument second-argument)
+ (list first-argument second-argument))
+";
+    assert_eq!(output(src), expected);
+    assert_eq!(output("(pprint '(short (list)))"), "(short (list))\n");
+}
+
+#[test]
+fn functions_display_their_parameters() {
+    let shown = eval("(fn (a b) (+ a b))").unwrap();
+    assert!(shown.contains('a') && shown.contains('b'), "{}", shown);
+    assert_eq!(shown, "<fn (a b)>");
+    assert_eq!(eval("(fn (x & xs) x)").unwrap(), "<fn (x & xs)>");
+}
+
+#[test]
+fn pairs_display_dotted() {
+    assert_eq!(eval("(cons 1 2)").unwrap(), "(1 . 2)");
+    assert_eq!(eval("(cons 1 (cons 2 3))").unwrap(), "(1 2 . 3)");
+    assert_eq!(eval("'(1 2 . 3)").unwrap(), "(1 2 . 3)");
+    assert_eq!(eval("(cdr '(1 . 2))").unwrap(), "2");
+}
+
+#[test]
+fn consing_onto_a_list_gives_a_list() {
+    assert_eq!(eval("(cons 1 (list 2 3))").unwrap(), "(1 2 3)");
+    assert_eq!(eval("(cons 1 (list))").unwrap(), "(1)");
+    assert_eq!(eval("'(1 . (2 3))").unwrap(), "(1 2 3)");
+}
+
+#[test]
+fn output_goes_to_the_environment_writer() {
+    let out = Rc::new(RefCell::new(Vec::new()));
+    let mut env = Env::default().with_writer(out.clone());
+    eval_str("(print 1) (println \" 2\") (pprint (list 3))", &mut env).unwrap();
+    assert_eq!(*out.borrow(), b"1 2\n(3)\n");
+    // closures called later write to the same place
+    eval_str("(defn say (x) (println x))", &mut env).unwrap();
+    out.borrow_mut().clear();
+    eval_str("(map say (list 4 5))", &mut env).unwrap();
+    assert_eq!(*out.borrow(), b"4\n5\n");
+}
+
+#[test]
+fn whole_floats_print_without_a_fraction() {
+    assert_eq!(eval("1.0").unwrap(), "1");
+    assert_eq!(eval("-0.0").unwrap(), "-0");
+    assert_eq!(output_in("(println 1.0)", precise(3)), "1\n");
+}
+
+#[test]
+fn float_precision_rounds_repeating_decimals() {
+    assert_eq!(output_in("(println (/ 1.0 3) (/ 2.0 3))", precise(3)), "0.333 0.667\n");
+}
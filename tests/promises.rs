@@ -0,0 +1,39 @@
+mod common;
+
+use common::{eval, eval_err, output};
+
+#[test]
+fn forcing_gives_the_value_of_the_expression() {
+    assert_eq!(eval("(force (delay (+ 1 2)))").unwrap(), "3");
+    assert_eq!(eval("(force 5)").unwrap(), "5");
+}
+
+#[test]
+fn delayed_side_effects_run_once_when_first_forced() {
+    assert_eq!(output("(def p (delay (print \"ran\"))) (print \"before\") (force p) (force p)"), "beforeran");
+    assert_eq!(eval("(def n 0) (def p (delay (set! n (+ n 1)))) (force p) (force p) n").unwrap(), "1");
+}
+
+#[test]
+fn promises_display_without_their_value() {
+    assert_eq!(eval("(delay 1)").unwrap(), "<promise>");
+    assert_eq!(eval("(def p (delay 1)) (force p) p").unwrap(), "<promise: forced>");
+}
+
+#[test]
+fn promises_containing_themselves_display() {
+    assert_eq!(eval("(def p (delay (list p))) (force p) p").unwrap(), "<promise: forced>");
+    assert_eq!(eval("(def p (delay (list p))) (force p)").unwrap(), "(<promise: forced>)");
+}
+
+#[test]
+fn forcing_a_promise_from_inside_itself_is_an_error() {
+    let err = eval_err("(def p (delay (force p))) (force p)");
+    assert_eq!(err.to_string(), "Cannot force a promise while it's already being forced");
+}
+
+#[test]
+fn a_failed_force_can_be_retried() {
+    let src = "(def ok false) (def p (delay (if ok 1 (error \"not yet\")))) (try (force p) 0) (set! ok true) (force p)";
+    assert_eq!(eval(src).unwrap(), "1");
+}
@@ -0,0 +1,59 @@
+use std::thread;
+
+use my_first_lisp::eval::{Env, EvalError};
+use my_first_lisp::eval_str;
+
+// test threads have small stacks, so these run with a limit well within them
+fn eval_limited(src: &str) -> Result<String, EvalError> {
+    let mut env = Env::default().with_recursion_limit(100);
+    eval_str(src, &mut env).map(|value| value.to_string())
+}
+
+#[test]
+fn infinite_recursion_hits_the_limit() {
+    let res = eval_limited("(defn f (n) (+ 1 (f n))) (f 1)");
+    assert_eq!(res, Err(EvalError::RecursionLimit));
+}
+
+#[test]
+fn tail_calls_in_if_branches_do_not_count_towards_the_limit() {
+    let src = "(defn count (n) (if (= n 0) 'done (count (- n 1)))) (count 100000)";
+    assert_eq!(eval_limited(src).unwrap(), "done");
+    let src = "(defn count (n) (if (!= n 0) (count (- n 1)) 'done)) (count 100000)";
+    assert_eq!(eval_limited(src).unwrap(), "done");
+}
+
+#[test]
+fn recursion_through_builtins_is_counted() {
+    for src in [
+        "(defn f (n) (if (= n 0) 0 (car (map f (list (- n 1)))))) (f 100000)",
+        "(defn f (n) (car (filter f (list n))))  (f 1)",
+        "(defn f (n) (reduce (fn (acc x) (f x)) 0 (list n))) (f 1)",
+        "(defn f (n) (sort (list 1 2) (fn (a b) (f a)))) (f 1)",
+        "(defn f (n) (apply f (list n))) (f 1)",
+        "(def f (memoize (fn (n) (f (+ n 1))))) (f 1)",
+    ] {
+        assert_eq!(eval_limited(src), Err(EvalError::RecursionLimit), "{}", src);
+    }
+}
+
+#[test]
+fn recursion_through_force_is_counted() {
+    let src = "(defn chain (n) (if (= n 0) (delay 0) (let ((p (chain (- n 1)))) (delay (+ 1 (force p))))))";
+    assert_eq!(eval_limited(&format!("{} (force (chain 10))", src)).unwrap(), "10");
+    assert_eq!(eval_limited(&format!("{} (force (chain 1000))", src)), Err(EvalError::RecursionLimit));
+}
+
+#[test]
+fn default_limit_fits_a_main_thread_stack() {
+    let main_thread = thread::Builder::new().stack_size(8 << 20);
+    let res = main_thread.spawn(|| {
+        [
+            "(defn f (n) (+ 1 (f n))) (f 1)",
+            "(defn f (n) (+ 1 (* 1 (- (+ 1 (car (list (f n)))) 0)))) (f 1)",
+            "(defn f (n) (car (map f (list n)))) (f 1)",
+            "(defn f (a :b :c) (+ 1 (f 1 :c 2 :b 3))) (f 1 :b 2 :c 3)",
+        ].map(|src| matches!(eval_str(src, &mut Env::default()), Err(EvalError::RecursionLimit)))
+    }).unwrap().join().unwrap();
+    assert_eq!(res, [true; 4]);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use my_first_lisp::eval::Env;
+use my_first_lisp::eval_str;
+
+use common::{eval, eval_err};
+
+#[test]
+fn special_forms_still_work_when_called_directly() {
+    assert_eq!(eval("(quote (1 2))").unwrap(), "(1 2)");
+    assert_eq!(eval("(def x 1) (set! x 2) x").unwrap(), "2");
+    assert_eq!(eval("(defn f (n) (* n 2)) (f 3)").unwrap(), "6");
+    assert_eq!(eval("(def n 0) (while (< n 3) (set! n (+ n 1))) n").unwrap(), "3");
+    assert_eq!(eval("(def n 0) (dotimes (i 4) (set! n (+ n i))) n").unwrap(), "6");
+    assert_eq!(eval("(force (delay (+ 1 2)))").unwrap(), "3");
+}
+
+#[test]
+fn special_forms_cannot_be_mapped() {
+    let err = eval_err("(map quote (list 1 2))");
+    assert_eq!(err.to_string(), "Cannot apply special form 'quote' to evaluated arguments");
+}
+
+#[test]
+fn special_forms_cannot_be_applied() {
+    for form in ["def", "defn", "set!", "fn", "delay", "while", "dotimes", "if", "let"] {
+        let err = eval_err(&format!("(apply {} (list 'x 1))", form));
+        assert_eq!(err.to_string(), format!("Cannot apply special form '{}' to evaluated arguments", form));
+    }
+}
+
+#[test]
+fn applying_def_defines_nothing() {
+    let mut env = Env::default();
+    let before = env.bindings().len();
+    assert!(eval_str("(apply def (list 'x 1))", &mut env).is_err());
+    assert_eq!(env.bindings().len(), before);
+}